@@ -0,0 +1,72 @@
+//! Times the debuff scanner with and without the perceptual hash index on a synthetic screen, for
+//! catalogs of several sizes.
+//!
+//! Usage: `cargo run --release --example bench_icon_index -- [runs]`
+
+use std::time::{Duration, Instant};
+
+use image::{imageops, Bgra, ImageBuffer};
+use image_match::{buff::DEFAULT_THRESHOLD, debuff::DebuffScanner, Matcher};
+
+/// Icons verified for each cell with the index, as by the debuff timer.
+const CANDIDATES: usize = 2;
+
+fn icon(i: u32) -> ImageBuffer<Bgra<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(32, 32, |x, y| {
+        let v = ((x * (i % 5 + 1) + y * (i / 5 + 1)) * 8 % 256) as u8;
+        Bgra([v, v / 2, 255 - v, 255])
+    })
+}
+
+fn time<T>(runs: u32, f: impl Fn() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    for _ in 1..runs {
+        f();
+    }
+    let result = f();
+    (result, start.elapsed() / runs)
+}
+
+fn main() {
+    let runs = std::env::args()
+        .nth(1)
+        .map(|x| x.parse().expect("invalid runs"))
+        .unwrap_or(50);
+
+    // Icons in both rows of the debuff area, which starts at 67 and whose columns start at
+    // (1366 - 3) % 32 = 19
+    let mut screen = ImageBuffer::from_pixel(1366, 768, Bgra([30, 30, 30, 255]));
+    let placed = [(0, 40, 0), (1, 39, 0), (2, 40, 1)];
+    for (i, column, row) in placed {
+        imageops::replace(&mut screen, &icon(i), 19 + 32 * column, 67 + 32 * row);
+    }
+
+    println!(
+        "{} runs on 1366x768, {} icons on the screen",
+        runs,
+        placed.len()
+    );
+    for size in [4, 16, 64] {
+        let catalog = || (0..size).map(|i| (i, icon(i)));
+        let plain = DebuffScanner::new(catalog(), DEFAULT_THRESHOLD, (1366, 768));
+        let indexed = plain.clone().with_index(CANDIDATES);
+
+        let (mut expected, plain_time) = time(runs, || plain.find(&screen).unwrap());
+        let (mut found, indexed_time) = time(runs, || indexed.find(&screen).unwrap());
+        expected.sort_by_key(|x| x.bounds);
+        found.sort_by_key(|x| x.bounds);
+        let same = expected
+            .iter()
+            .map(|x| (x.kind, x.bounds))
+            .eq(found.iter().map(|x| (x.kind, x.bounds)));
+        println!(
+            "  {:>2} icons: {:>8.2?} without the index, {:>8.2?} with {} candidates ({:.1}x){}",
+            size,
+            plain_time,
+            indexed_time,
+            CANDIDATES,
+            plain_time.as_secs_f64() / indexed_time.as_secs_f64(),
+            if same { "" } else { ", results differ" }
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! Measures the accuracy and speed of the matchers on a labelled dataset of screenshots.
+//!
+//! Usage: `cargo run --release --example eval_dataset -- [manifest]`, where the manifest defaults
+//! to `example_assets/dataset/manifest.json`.
+//!
+//! The manifest lists screenshots, relative to the manifest, with the expected results of each
+//! matcher. A missing label is not evaluated, and `null` means that the matcher must not match.
+//! `ratio` of `boss_hp` is the HP ratio of the current layer, and the keys of `buffs` are asset
+//! ids of buff icons. Screenshots which are not found are skipped with a warning.
+//!
+//! A boss HP result on the wrong layer counts as a miss. The reap frame error is measured on the
+//! first frame found, as the timer sees it, and the number of frames matching each screenshot is
+//! reported separately.
+//!
+//! ```json
+//! {
+//!     "samples": [
+//!         {
+//!             "image": "jinhillah/phase2.png",
+//!             "resolution": [1366, 768],
+//!             "boss_hp": { "layer": 2, "ratio": 0.53 },
+//!             "reap_frame": null,
+//!             "buffs": { "v_buficon": true }
+//!         }
+//!     ]
+//! }
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use assets_embedded::assets;
+use assets_manager::asset::Png;
+use image::{Bgra, ImageBuffer};
+use image_match::{
+    boss_hp::{BossHpBarMatcher, JIN_HILLAH},
+    buff::{BuffMatcher, DEFAULT_THRESHOLD},
+    jinhillah::JinHillahReapMatcher,
+    Matcher,
+};
+use serde::{Deserialize, Deserializer};
+
+type Screen = ImageBuffer<Bgra<u8>, Vec<u8>>;
+
+#[derive(Deserialize)]
+struct Manifest {
+    samples: Vec<Sample>,
+}
+
+#[derive(Deserialize)]
+struct Sample {
+    image: PathBuf,
+    resolution: (u32, u32),
+    #[serde(default, deserialize_with = "labelled")]
+    boss_hp: Option<Option<HpLabel>>,
+    #[serde(default, deserialize_with = "labelled")]
+    reap_frame: Option<Option<usize>>,
+    #[serde(default)]
+    buffs: BTreeMap<String, bool>,
+}
+
+#[derive(Deserialize)]
+struct HpLabel {
+    layer: usize,
+    ratio: f64,
+}
+
+/// Tells a `null` label from a missing one, which `Option` alone does not.
+fn labelled<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Detection counts, errors of the results and timings of a matcher.
+#[derive(Default)]
+struct Report {
+    true_positives: usize,
+    false_positives: usize,
+    false_negatives: usize,
+    true_negatives: usize,
+    /// Results which are off in a way not measured by `errors`, e.g. a wrong layer.
+    wrong: usize,
+    errors: Vec<f64>,
+    timings: Vec<f64>,
+}
+
+impl Report {
+    fn record(&mut self, expected: bool, found: bool, elapsed: Duration) {
+        match (expected, found) {
+            (true, true) => self.true_positives += 1,
+            (false, true) => self.false_positives += 1,
+            (true, false) => self.false_negatives += 1,
+            (false, false) => self.true_negatives += 1,
+        }
+        self.timings.push(elapsed.as_secs_f64() * 1000.0);
+    }
+
+    fn print(&mut self, name: &str, error: &str) {
+        let ratio = |a: usize, b: usize| {
+            if a + b == 0 {
+                String::from("-")
+            } else {
+                format!("{:.3}", a as f64 / (a + b) as f64)
+            }
+        };
+        println!("{}", name);
+        println!(
+            "  precision {} recall {} (tp {}, fp {}, fn {}, tn {})",
+            ratio(self.true_positives, self.false_positives),
+            ratio(self.true_positives, self.false_negatives),
+            self.true_positives,
+            self.false_positives,
+            self.false_negatives,
+            self.true_negatives,
+        );
+        if self.wrong > 0 {
+            println!("  wrong results: {}", self.wrong);
+        }
+        if !self.errors.is_empty() {
+            println!("  {}: {}", error, distribution(&mut self.errors, 4));
+        }
+        if !self.timings.is_empty() {
+            println!("  time (ms): {}", distribution(&mut self.timings, 2));
+        }
+    }
+}
+
+fn distribution(values: &mut [f64], precision: usize) -> String {
+    values.sort_by(f64::total_cmp);
+    let percentile = |p: f64| values[((values.len() - 1) as f64 * p).round() as usize];
+    format!(
+        "mean {:.*}, median {:.*}, p90 {:.*}, max {:.*} (n = {})",
+        precision,
+        values.iter().sum::<f64>() / values.len() as f64,
+        precision,
+        percentile(0.5),
+        precision,
+        percentile(0.9),
+        precision,
+        values[values.len() - 1],
+        values.len()
+    )
+}
+
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let ret = f();
+    (ret, start.elapsed())
+}
+
+fn load_icon(id: &str) -> Screen {
+    assets()
+        .load::<Png>(id)
+        .unwrap_or_else(|e| panic!("failed to load buff icon {}: {}", id, e))
+        .cloned()
+        .0
+        .to_bgra8()
+}
+
+fn main() {
+    let manifest_path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("example_assets/dataset/manifest.json"));
+    let manifest_path = Path::new(&manifest_path);
+    let manifest: Manifest =
+        serde_json::from_reader(std::fs::File::open(manifest_path).expect("no manifest"))
+            .expect("invalid manifest");
+    let dir = manifest_path.parent().unwrap();
+
+    <BossHpBarMatcher as Matcher<Screen>>::init();
+    <JinHillahReapMatcher as Matcher<Screen>>::init();
+    <BuffMatcher as Matcher<Screen>>::init();
+
+    let mut reap_matchers = HashMap::new();
+    let mut buff_matchers = HashMap::new();
+    let (mut hp, mut reap) = (Report::default(), Report::default());
+    let mut reap_frame_counts = Vec::new();
+    let mut buffs = BTreeMap::<String, Report>::new();
+    let mut missing = 0;
+
+    for sample in &manifest.samples {
+        let path = dir.join(&sample.image);
+        if !path.exists() {
+            println!("warning: {} is missing", sample.image.display());
+            missing += 1;
+            continue;
+        }
+        let img = image::open(&path)
+            .unwrap_or_else(|e| panic!("failed to read {}: {}", path.display(), e))
+            .to_bgra8();
+        if img.dimensions() != sample.resolution {
+            println!(
+                "warning: {} is {:?}, not {:?}",
+                sample.image.display(),
+                img.dimensions(),
+                sample.resolution
+            );
+        }
+
+        if let Some(label) = &sample.boss_hp {
+            // A new matcher for each screenshot, since the colors are calibrated on the first one
+            let matcher = BossHpBarMatcher::new(JIN_HILLAH).expect("no boss icon");
+            let (result, elapsed) = timed(|| matcher.find(&img));
+            let wrong_layer = matches!(
+                (label, &result),
+                (Some(label), Some(result)) if result.layer() != label.layer
+            );
+            hp.record(label.is_some(), result.is_some() && !wrong_layer, elapsed);
+            if let (Some(label), Some(result)) = (label, result) {
+                if result.layer() == label.layer {
+                    hp.errors.push((result.hp_ratio() - label.ratio).abs());
+                } else {
+                    hp.wrong += 1;
+                    println!(
+                        "{}: layer {} instead of {}",
+                        sample.image.display(),
+                        result.layer(),
+                        label.layer
+                    );
+                }
+            }
+        }
+
+        if let Some(label) = &sample.reap_frame {
+            let matcher = reap_matchers
+                .entry(sample.resolution)
+                .or_insert_with(|| JinHillahReapMatcher::new(sample.resolution));
+            let (frame, elapsed) = timed(|| matcher.find(&img));
+            reap.record(label.is_some(), frame.is_some(), elapsed);
+            if let (Some(label), Some(frame)) = (label, frame) {
+                reap.errors.push(frame.abs_diff(*label) as f64);
+            }
+            let frames = matcher
+                .candidates_iter(&img)
+                .flat_map(|x| matcher.matching_frames(&x))
+                .collect::<BTreeSet<_>>();
+            if !frames.is_empty() {
+                reap_frame_counts.push(frames.len() as f64);
+            }
+        }
+
+        for (id, expected) in &sample.buffs {
+            let matcher = buff_matchers
+                .entry((id.clone(), sample.resolution))
+                .or_insert_with(|| {
+                    BuffMatcher::new(load_icon(id), DEFAULT_THRESHOLD, sample.resolution)
+                });
+            let (result, elapsed) = timed(|| matcher.find(&img));
+            buffs
+                .entry(id.clone())
+                .or_default()
+                .record(*expected, result.is_some(), elapsed);
+        }
+    }
+
+    println!("{} samples, {} missing\n", manifest.samples.len(), missing);
+    hp.print("boss HP bar", "ratio error");
+    reap.print("reap", "frame error");
+    if !reap_frame_counts.is_empty() {
+        println!(
+            "  frames matched: {}",
+            distribution(&mut reap_frame_counts, 1)
+        );
+    }
+    for (id, report) in &mut buffs {
+        report.print(&format!("buff {}", id), "error");
+    }
+}
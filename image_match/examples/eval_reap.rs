@@ -0,0 +1,91 @@
+//! Reports which reap frames detect the screenshots in `example_assets/reap`.
+//!
+//! Usage: `cargo run --example eval_reap -- [scale] [max_mismatch]`
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use assets_manager::{asset::Png, AssetCache};
+use image::{Bgra, ImageBuffer};
+use image_match::{
+    jinhillah::{JinHillahReapMatcher, ReapTemplateConfig},
+    Matcher,
+};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut config = ReapTemplateConfig::default();
+    if let Some(scale) = args.next() {
+        config.scale = scale.parse().expect("invalid scale");
+    }
+    if let Some(max_mismatch) = args.next() {
+        config.max_mismatch = max_mismatch.parse().expect("invalid max_mismatch");
+    }
+    println!("{:?}", config);
+
+    let assets = AssetCache::new("example_assets").unwrap();
+    <JinHillahReapMatcher as Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>>::init();
+    let reap_matcher = JinHillahReapMatcher::with_config((1280, 720), &config);
+
+    let imgs = assets
+        .load_dir::<Png>("reap", false)
+        .unwrap()
+        .iter()
+        .map(Result::unwrap)
+        .collect::<Vec<_>>();
+
+    let mut detections = BTreeMap::<String, BTreeSet<usize>>::new();
+    for img in imgs {
+        let name = img.id().to_owned();
+        let img = img.cloned().0.to_bgra8();
+        let frames = reap_matcher
+            .candidates_iter(&img)
+            .flat_map(|x| reap_matcher.matching_frames(&x))
+            .collect::<BTreeSet<_>>();
+        println!("{}: {:?}", name, frames);
+        detections.insert(name, frames);
+    }
+
+    let mut counts = BTreeMap::<usize, usize>::new();
+    for frame in detections.values().flatten() {
+        *counts.entry(*frame).or_default() += 1;
+    }
+    println!("\nframe: detected screenshots");
+    for (frame, count) in &counts {
+        println!("{:03}: {}", frame, count);
+    }
+
+    let missed = detections
+        .iter()
+        .filter(|(_, frames)| frames.is_empty())
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    println!("\nmissed: {:?}", missed);
+
+    // Greedily pick frames until every detected screenshot is covered
+    let mut uncovered = detections
+        .iter()
+        .filter(|(_, frames)| !frames.is_empty())
+        .map(|(name, _)| name.as_str())
+        .collect::<BTreeSet<_>>();
+    let mut subset = Vec::new();
+    while !uncovered.is_empty() {
+        let (frame, covered) = counts
+            .keys()
+            .map(|frame| {
+                let covered = uncovered
+                    .iter()
+                    .filter(|name| detections[**name].contains(frame))
+                    .copied()
+                    .collect::<Vec<_>>();
+                (*frame, covered)
+            })
+            .max_by_key(|(_, covered)| covered.len())
+            .unwrap();
+        for name in covered {
+            uncovered.remove(name);
+        }
+        subset.push(frame);
+    }
+    subset.sort_unstable();
+    println!("suggested frames: {:?}", subset);
+}
@@ -1,7 +1,7 @@
 use assets_embedded::assets;
 use assets_manager::{asset::Png, AssetCache};
-use image::{Bgra, DynamicImage, GenericImageView, ImageBuffer};
-use image_match::{buff::BuffMatcher, Matcher};
+use image::{Bgra, DynamicImage, ImageBuffer};
+use image_match::{buff::BuffMatcher, debug, Matcher};
 
 fn main() {
     let examples = AssetCache::new("example_assets").unwrap();
@@ -23,37 +23,23 @@ fn main() {
         .cloned()
         .0
         .to_bgra8();
-    let mut palette = img.clone();
-    let colors = vec![
-        Bgra([255u8, 0u8, 0u8, 255u8]),
-        Bgra([0u8, 255u8, 0u8, 255u8]),
-        Bgra([0u8, 0u8, 255u8, 255u8]),
-        Bgra([255u8, 255u8, 0u8, 255u8]),
-        Bgra([0u8, 255u8, 255u8, 255u8]),
-        Bgra([255u8, 0u8, 255u8, 255u8]),
-    ];
-    buff_matcher
-        .candidates_iter(&img)
-        .enumerate()
-        .map(|(i, img)| {
-            let (x, y, w, h) = img.bounds();
-            let check = buff_matcher.check(&img);
-            assert_eq!(w, 32);
-            assert_eq!(h, 32);
-            for x in x..(x + w) {
-                for y in y..(y + h) {
-                    *palette.get_pixel_mut(x, y) = colors[i as usize % 6];
-                }
-            }
-            (img, check)
-        })
-        .filter(|x| x.1)
-        .for_each(|x| {
-            let result = buff_matcher.match_image(&x.0);
-            println!("bounds: {:?}, ok: {}", x.0.bounds(), result.is_some());
-        });
+    let (_, report) = debug::trace_with_score(&buff_matcher, &img, |view, _| {
+        Some(buff_matcher.score(view))
+    });
+    for candidate in report
+        .candidates
+        .iter()
+        .filter(|x| x.outcome != debug::Outcome::Rejected)
+    {
+        println!(
+            "bounds: {:?}, outcome: {:?}, result: {:?}",
+            candidate.bounds, candidate.outcome, candidate.result
+        );
+    }
 
-    DynamicImage::ImageBgra8(palette)
+    let mut annotated = img.clone();
+    report.annotate(&mut annotated);
+    DynamicImage::ImageBgra8(annotated)
         .to_rgba8()
         .save("out/buff.png")
         .unwrap();
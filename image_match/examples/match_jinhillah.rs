@@ -1,14 +1,13 @@
 use assets_manager::{asset::Png, AssetCache};
-use image::{Bgra, GenericImageView, ImageBuffer};
+use image::GenericImageView;
 use image_match::{
-    jinhillah::{JinHillahHpMatchResult, JinHillahHpMatcher},
+    boss_hp::{BossHpBarMatchResult, BossHpBarMatcher, JIN_HILLAH},
     Matcher,
 };
 
 fn main() {
     let assets = AssetCache::new("example_assets").unwrap();
-    let hp_matcher = JinHillahHpMatcher;
-    <JinHillahHpMatcher as Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>>::init();
+    let hp_matcher = BossHpBarMatcher::new(JIN_HILLAH).unwrap();
 
     let imgs = assets
         .load_dir::<Png>("bars", false)
@@ -34,7 +33,7 @@ fn main() {
                     "name: {}, {:?}, ratio {:?}",
                     name,
                     result,
-                    result.as_ref().map(JinHillahHpMatchResult::hp_ratio)
+                    result.as_ref().map(BossHpBarMatchResult::hp_ratio)
                 );
             })
     }
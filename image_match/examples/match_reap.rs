@@ -7,8 +7,8 @@ use image_match::{jinhillah::JinHillahReapMatcher, Matcher};
 fn main() {
     let assets = AssetCache::new("example_assets").unwrap();
 
-    let reap_matcher = JinHillahReapMatcher(1280, 720);
     <JinHillahReapMatcher as Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>>::init();
+    let reap_matcher = JinHillahReapMatcher::new((1280, 720));
     let imgs = assets
         .load_dir::<Png>("reap", false)
         .unwrap()
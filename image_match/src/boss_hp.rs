@@ -0,0 +1,596 @@
+use std::{
+    cell::{Cell, RefCell},
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use assets_embedded::assets;
+use assets_manager::asset::Png;
+use image::{Bgra, GenericImageView, ImageBuffer, SubImage};
+
+use crate::{
+    color::{ColorTransform, Lab},
+    view_ext::GenericImageViewExt,
+    Matcher,
+};
+
+/// Geometry of a boss HP bar, relative to the top left corner of the bar.
+#[derive(Debug, Clone)]
+pub struct BossHpBar {
+    /// `(width, height)` of the whole bar including the icon.
+    pub dims: (u32, u32),
+    /// Position of the boss icon.
+    pub icon_offset: (u32, u32),
+    /// Horizontal span of the HP gauge, inclusive of `x.end`.
+    pub x: Range<u32>,
+    /// Upper of the two rows sampled on the gauge.
+    pub y: u32,
+    /// Upper of the two rows sampled on the last column, which is rounded on some bars.
+    pub end_y: u32,
+}
+
+/// Colors of the two sampled rows.
+type ColorPair = (Bgra<u8>, Bgra<u8>);
+
+/// Describes the HP bar of a boss.
+#[derive(Debug, Clone)]
+pub struct BossDescriptor {
+    /// Asset id of the icon on the left end of the bar.
+    pub icon: &'static str,
+    /// Colors of the two sampled rows for each layer of the bar, from the top layer. The number of
+    /// layers of the bar is the length of this.
+    pub layers: &'static [(Bgra<u8>, Bgra<u8>)],
+    pub bar: BossHpBar,
+}
+
+pub const JIN_HILLAH: BossDescriptor = BossDescriptor {
+    icon: "jinhillah_boss_hpbar_icon",
+    layers: &[
+        (Bgra([102, 68, 204, 255]), Bgra([102, 68, 187, 255])),
+        (Bgra([153, 102, 238, 255]), Bgra([153, 102, 221, 255])),
+        (Bgra([34, 170, 170, 255]), Bgra([17, 153, 136, 255])),
+        (Bgra([17, 119, 85, 255]), Bgra([17, 102, 68, 255])),
+    ],
+    bar: BossHpBar {
+        dims: (800, 37),
+        icon_offset: (3, 3),
+        // Note: Y is 9/10 and 8/9 for x = 1035
+        x: 40..796,
+        y: 9,
+        end_y: 8,
+    },
+};
+
+/// Options of [`BossHpBarMatcher`].
+#[derive(Debug, Clone)]
+pub struct BossHpBarConfig {
+    /// Maximum CIE76 difference of a pixel from the colors of the bar or the icon.
+    pub tolerance: f64,
+    /// Ratio of the icon pixels allowed to differ more than `tolerance`.
+    pub icon_mismatch: f64,
+    /// Learn the colors of the bar on the screen from the first confident frame, so that the
+    /// tolerance only has to cover the noise.
+    pub calibrate: bool,
+}
+
+impl Default for BossHpBarConfig {
+    fn default() -> Self {
+        Self {
+            tolerance: 12.0,
+            icon_mismatch: 0.05,
+            calibrate: true,
+        }
+    }
+}
+
+/// Handle to make a [`BossHpBarMatcher`] learn the colors again from other threads, as the color
+/// filter of the screen may have changed since the last fight.
+#[derive(Debug, Clone)]
+pub struct CalibrationReset(Arc<AtomicBool>);
+
+impl CalibrationReset {
+    pub fn reset(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Reads the HP bar of the boss described by a [`BossDescriptor`].
+///
+/// Colors are compared by perceptual difference, so the bar is read through color filters of the
+/// screen as long as they stay within [`BossHpBarConfig::tolerance`].
+pub struct BossHpBarMatcher {
+    boss: BossDescriptor,
+    icon: ImageBuffer<Bgra<u8>, Vec<u8>>,
+    icon_lab: Vec<Lab>,
+    config: BossHpBarConfig,
+    /// Colors of the layers, replaced by the calibrated ones once learned.
+    layers: RefCell<Vec<(Lab, Lab)>>,
+    calibrated: Cell<bool>,
+    reset_requested: Arc<AtomicBool>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BossHpBarMatchResult {
+    layer: usize,
+    remaining_pixels: u32,
+    max_pixels: u32,
+    layers: usize,
+    calibrated: bool,
+}
+
+impl BossHpBarMatchResult {
+    /// 1-based index of the layer being depleted, counting from the top. A full layer is the one
+    /// being depleted, and an empty bar is on the last layer.
+    pub fn layer(&self) -> usize {
+        self.layer
+    }
+
+    /// HP ratio of the current layer.
+    pub fn hp_ratio(&self) -> f64 {
+        self.remaining_pixels as f64 / self.max_pixels as f64
+    }
+
+    /// HP ratio of the whole bar, counting the layers beneath. Continuous across layers, and 0 for
+    /// an empty bar.
+    pub fn total_hp_ratio(&self) -> f64 {
+        ((self.layers - self.layer()) as f64 + self.hp_ratio()) / self.layers as f64
+    }
+
+    /// Whether the bar was read with the calibrated colors.
+    pub fn calibrated(&self) -> bool {
+        self.calibrated
+    }
+}
+
+/// Segments of the bar shorter than this are not used for calibration.
+const MIN_CALIBRATION_PIXELS: usize = 50;
+/// Pixels of a segment used for calibration must be this close to their mean.
+const MAX_CALIBRATION_SPREAD: f64 = 3.0;
+
+/// Clones learn the colors on their own, and are not reset by the [`CalibrationReset`] of the
+/// original.
+impl Clone for BossHpBarMatcher {
+    fn clone(&self) -> Self {
+        Self {
+            boss: self.boss.clone(),
+            icon: self.icon.clone(),
+            icon_lab: self.icon_lab.clone(),
+            config: self.config.clone(),
+            layers: self.layers.clone(),
+            calibrated: self.calibrated.clone(),
+            reset_requested: Arc::new(AtomicBool::new(self.reset_requested.load(Ordering::SeqCst))),
+        }
+    }
+}
+
+impl BossHpBarMatcher {
+    /// Returns `None` if the icon asset of the boss is missing.
+    pub fn new(boss: BossDescriptor) -> Option<Self> {
+        Self::with_config(boss, BossHpBarConfig::default())
+    }
+
+    pub fn with_config(boss: BossDescriptor, config: BossHpBarConfig) -> Option<Self> {
+        let icon = assets().load::<Png>(boss.icon).ok()?.cloned().0.to_bgra8();
+        Some(Self::with_icon(boss, icon, config))
+    }
+
+    fn with_icon(
+        boss: BossDescriptor,
+        icon: ImageBuffer<Bgra<u8>, Vec<u8>>,
+        config: BossHpBarConfig,
+    ) -> Self {
+        let icon_lab = icon.pixels().map(|p| Lab::from_bgra(*p)).collect();
+        Self {
+            layers: RefCell::new(Self::reference_layers(&boss)),
+            boss,
+            icon,
+            icon_lab,
+            config,
+            calibrated: Cell::new(false),
+            reset_requested: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn reference_layers(boss: &BossDescriptor) -> Vec<(Lab, Lab)> {
+        boss.layers
+            .iter()
+            .map(|(a, b)| (Lab::from_bgra(*a), Lab::from_bgra(*b)))
+            .collect()
+    }
+
+    pub fn boss(&self) -> &BossDescriptor {
+        &self.boss
+    }
+
+    /// Returns a handle that discards the learned colors on the next screen.
+    pub fn calibration_reset(&self) -> CalibrationReset {
+        CalibrationReset(Arc::clone(&self.reset_requested))
+    }
+
+    /// Index of the nearest layer, its difference from the colors, and the difference of the second
+    /// nearest one.
+    fn nearest_layer(&self, pair: ColorPair) -> (usize, f64, f64) {
+        let (top, bottom) = (Lab::from_bgra(pair.0), Lab::from_bgra(pair.1));
+        let mut diffs = self
+            .layers
+            .borrow()
+            .iter()
+            .map(|(a, b)| a.delta_e(&top).max(b.delta_e(&bottom)))
+            .enumerate()
+            .collect::<Vec<_>>();
+        diffs.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let second = diffs.get(1).map_or(f64::INFINITY, |x| x.1);
+        (diffs[0].0, diffs[0].1, second)
+    }
+
+    /// Index of the layer with the colors, or the number of layers if it is the background.
+    fn find_color(&self, pair: ColorPair) -> usize {
+        match self.nearest_layer(pair) {
+            (i, diff, _) if diff <= self.config.tolerance => i,
+            _ => self.boss.layers.len(),
+        }
+    }
+
+    /// Learns the colors of the layers from the segments of the bar, if they are uniform and
+    /// clearly of a single layer. The layers not on the screen are mapped through the same
+    /// per-channel transform.
+    fn calibrate(&self, pairs: &[ColorPair], segments: &[(Range<usize>, usize)]) {
+        let mean = |colors: &mut dyn Iterator<Item = Bgra<u8>>| {
+            let (mut sum, mut n) = ([0u32; 3], 0);
+            for p in colors {
+                for (s, c) in sum.iter_mut().zip(p.0) {
+                    *s += c as u32;
+                }
+                n += 1;
+            }
+            Bgra([
+                (sum[0] / n) as u8,
+                (sum[1] / n) as u8,
+                (sum[2] / n) as u8,
+                255,
+            ])
+        };
+
+        let mut samples = Vec::new();
+        for (range, layer) in segments {
+            if *layer >= self.boss.layers.len() || range.len() < MIN_CALIBRATION_PIXELS {
+                continue;
+            }
+            let segment = &pairs[range.clone()];
+            let top = mean(&mut segment.iter().map(|x| x.0));
+            let bottom = mean(&mut segment.iter().map(|x| x.1));
+            let (top_lab, bottom_lab) = (Lab::from_bgra(top), Lab::from_bgra(bottom));
+            let uniform = segment.iter().all(|(a, b)| {
+                Lab::from_bgra(*a).delta_e(&top_lab) <= MAX_CALIBRATION_SPREAD
+                    && Lab::from_bgra(*b).delta_e(&bottom_lab) <= MAX_CALIBRATION_SPREAD
+            });
+            let (_, diff, second) = self.nearest_layer((top, bottom));
+            // Another layer within the tolerance makes it ambiguous
+            if !uniform || diff > self.config.tolerance || second <= self.config.tolerance {
+                return;
+            }
+            let (ref_top, ref_bottom) = self.boss.layers[*layer];
+            samples.push((ref_top, top));
+            samples.push((ref_bottom, bottom));
+        }
+
+        if let Some(transform) = ColorTransform::fit(&samples) {
+            *self.layers.borrow_mut() = self
+                .boss
+                .layers
+                .iter()
+                .map(|(a, b)| {
+                    (
+                        Lab::from_bgra(transform.apply(*a)),
+                        Lab::from_bgra(transform.apply(*b)),
+                    )
+                })
+                .collect();
+            self.calibrated.set(true);
+        }
+    }
+}
+
+impl<V: GenericImageView<Pixel = Bgra<u8>>> Matcher<V> for BossHpBarMatcher {
+    type MatchResult = BossHpBarMatchResult;
+    type CandidatesIter<'a> = impl Iterator<Item = SubImage<&'a V::InnerImageView>> + 'a where V: 'a;
+
+    fn view_dimensions(&self) -> (u32, u32) {
+        self.boss.bar.dims
+    }
+
+    fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a> {
+        let icon_height = self.icon.height();
+        view.view(0, 0, view.width(), icon_height)
+            .view_bounds_like((Matcher::<V>::view_dimensions(self).0, icon_height), 1)
+            .map(move |(x, y, w, h)| view.view(x, y, w, h))
+    }
+
+    fn check<'a>(&self, view: &SubImage<&'a V>) -> bool {
+        let (x, y) = self.boss.bar.icon_offset;
+        let max_misses = (self.config.icon_mismatch * self.icon_lab.len() as f64) as usize;
+        let mut misses = 0;
+        for ((ix, iy, p), lab) in self.icon.enumerate_pixels().zip(&self.icon_lab) {
+            let q = view.get_pixel(x + ix, y + iy);
+            if q == *p || Lab::from_bgra(q).delta_e(lab) <= self.config.tolerance {
+                continue;
+            }
+            misses += 1;
+            if misses > max_misses {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        if self.reset_requested.swap(false, Ordering::SeqCst) {
+            *self.layers.borrow_mut() = Self::reference_layers(&self.boss);
+            self.calibrated.set(false);
+        }
+
+        let bar = &self.boss.bar;
+        let max_pixels = bar.x.end - bar.x.start + 1;
+        let pairs = bar
+            .x
+            .clone()
+            .map(|x| (view.get_pixel(x, bar.y), view.get_pixel(x, bar.y + 1)))
+            .chain(std::iter::once((
+                view.get_pixel(bar.x.end, bar.end_y),
+                view.get_pixel(bar.x.end, bar.end_y + 1),
+            )))
+            .collect::<Vec<_>>();
+
+        // The bar is mostly long runs of the same colors
+        let mut last: Option<(ColorPair, usize)> = None;
+        let idxs = pairs
+            .iter()
+            .map(|&pair| match last {
+                Some((p, idx)) if p == pair => idx,
+                _ => {
+                    let idx = self.find_color(pair);
+                    last = Some((pair, idx));
+                    idx
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let changes = (1..idxs.len())
+            .filter(|&i| idxs[i] != idxs[i - 1])
+            .collect::<Vec<_>>();
+        if changes.len() > 1 {
+            return None;
+        }
+        let changed_x = changes.first().copied();
+
+        if self.config.calibrate && !self.calibrated.get() {
+            let segments = match changed_x {
+                Some(i) => vec![(0..i, idxs[0]), (i..idxs.len() - 1, idxs[i])],
+                None => vec![(0..idxs.len() - 1, idxs[0])],
+            };
+            self.calibrate(&pairs, &segments);
+        }
+
+        // The left end is always of the layer being depleted, or the background if the bar is empty
+        let layers = self.boss.layers.len();
+        let (layer, remaining_pixels) = match changed_x {
+            Some(_) if idxs[0] >= layers => return None,
+            Some(i) => (idxs[0] + 1, i as u32),
+            None if idxs[0] >= layers => (layers, 0),
+            None => (idxs[0] + 1, max_pixels),
+        };
+
+        Some(BossHpBarMatchResult {
+            layer,
+            remaining_pixels,
+            max_pixels,
+            layers,
+            calibrated: self.calibrated.get(),
+        })
+    }
+}
+
+/// How a boss fight ended, detected by [`BossFightTracker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BossEvent {
+    /// The HP bar disappeared after reaching zero.
+    Defeated,
+    /// The HP bar disappeared with HP left, by a party wipe or leaving the map.
+    Left,
+}
+
+/// Detects the end of a boss fight from the HP bar readings.
+#[derive(Debug, Clone, Default)]
+pub struct BossFightTracker {
+    /// Instant of the last reading of the current fight, and whether the boss was nearly dead.
+    last: Option<(Instant, bool)>,
+}
+
+impl BossFightTracker {
+    /// The fight is over when the HP bar is not seen for this long.
+    const GONE_AFTER: Duration = Duration::from_secs(3);
+    /// HP ratio of the last layer regarded as zero. The bar fades out on the killing blow, which
+    /// may take the last few percent at once.
+    const DEFEAT_RATIO: f64 = 0.05;
+
+    pub fn push(&mut self, at: Instant, result: &BossHpBarMatchResult) {
+        let nearly_dead =
+            result.layer() == result.layers && result.hp_ratio() <= Self::DEFEAT_RATIO;
+        self.last = Some((at, nearly_dead));
+    }
+
+    pub fn in_fight(&self) -> bool {
+        self.last.is_some()
+    }
+
+    /// Returns the event once when the HP bar has been gone for a while.
+    pub fn poll(&mut self, now: Instant) -> Option<BossEvent> {
+        let (at, nearly_dead) = self.last?;
+        if now.saturating_duration_since(at) < Self::GONE_AFTER {
+            return None;
+        }
+        self.last = None;
+        if nearly_dead {
+            Some(BossEvent::Defeated)
+        } else {
+            Some(BossEvent::Left)
+        }
+    }
+}
+
+#[cfg(test)]
+const TEST_BOSS: BossDescriptor = BossDescriptor {
+    icon: "",
+    layers: JIN_HILLAH.layers,
+    bar: BossHpBar {
+        dims: (200, 12),
+        icon_offset: (1, 1),
+        x: 20..196,
+        y: 5,
+        end_y: 5,
+    },
+};
+
+#[cfg(test)]
+fn test_icon() -> ImageBuffer<Bgra<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(8, 8, |x, y| Bgra([(x * 30) as u8, (y * 30) as u8, 90, 255]))
+}
+
+/// HP bar of [`TEST_BOSS`] with the colors of the layer at each x of the gauge, or the background.
+#[cfg(test)]
+fn test_bar(layer_at: impl Fn(u32) -> Option<usize>) -> ImageBuffer<Bgra<u8>, Vec<u8>> {
+    let mut img = ImageBuffer::from_pixel(200, 12, Bgra([0, 0, 0, 255]));
+    for (x, y, p) in test_icon().enumerate_pixels() {
+        img.put_pixel(x + 1, y + 1, *p);
+    }
+    for x in 20..=196 {
+        if let Some(layer) = layer_at(x) {
+            let (top, bottom) = TEST_BOSS.layers[layer];
+            img.put_pixel(x, 5, top);
+            img.put_pixel(x, 6, bottom);
+        }
+    }
+    img
+}
+
+#[test]
+fn hp_bar_layers() {
+    let matcher = BossHpBarMatcher::with_icon(TEST_BOSS, test_icon(), BossHpBarConfig::default());
+    let read = |layer_at: &dyn Fn(u32) -> Option<usize>| {
+        let result = matcher.find(&test_bar(layer_at)).unwrap();
+        (result.layer(), result.hp_ratio(), result.total_hp_ratio())
+    };
+
+    // Full bar
+    assert_eq!(read(&|_| Some(0)), (1, 1.0, 1.0));
+    // Across a layer boundary, the total ratio does not jump
+    let (layer, _, before) = read(&|x| Some(if x < 21 { 1 } else { 2 }));
+    assert_eq!(layer, 2);
+    let (layer, ratio, after) = read(&|_| Some(2));
+    assert_eq!((layer, ratio), (3, 1.0));
+    assert!((before - after).abs() < 0.01);
+    // The last layer, and the empty bar
+    let (layer, _, total) = read(&|x| if x < 60 { Some(3) } else { None });
+    assert_eq!(layer, 4);
+    assert!(total > 0.0 && total < 0.25);
+    assert_eq!(read(&|_| None), (4, 0.0, 0.0));
+}
+
+#[test]
+fn boss_fight_events() {
+    let matcher = BossHpBarMatcher::with_icon(TEST_BOSS, test_icon(), BossHpBarConfig::default());
+    let read = |layer_at: &dyn Fn(u32) -> Option<usize>| matcher.find(&test_bar(layer_at)).unwrap();
+    let start = Instant::now();
+    let secs = |x| start + Duration::from_secs(x);
+    let mut tracker = BossFightTracker::default();
+    assert_eq!(tracker.poll(secs(10)), None);
+
+    // A few pixels of the last layer left before the bar fades
+    tracker.push(secs(0), &read(&|_| Some(0)));
+    tracker.push(secs(1), &read(&|x| if x < 24 { Some(3) } else { None }));
+    assert_eq!(tracker.poll(secs(2)), None);
+    assert_eq!(tracker.poll(secs(5)), Some(BossEvent::Defeated));
+    assert_eq!(tracker.poll(secs(6)), None);
+
+    tracker.push(secs(7), &read(&|_| None));
+    assert_eq!(tracker.poll(secs(11)), Some(BossEvent::Defeated));
+
+    // A few pixels of an upper layer are not a kill
+    tracker.push(secs(12), &read(&|x| if x < 24 { Some(2) } else { Some(3) }));
+    assert!(tracker.in_fight());
+    assert_eq!(tracker.poll(secs(16)), Some(BossEvent::Left));
+    assert!(!tracker.in_fight());
+}
+
+#[test]
+fn filtered_hp_bar() {
+    const BOSS: BossDescriptor = TEST_BOSS;
+    let icon = test_icon();
+    // Night light like filter, which makes blue darker and red brighter
+    let filter = |p: Bgra<u8>| {
+        Bgra([
+            (p.0[0] as f64 * 0.92) as u8,
+            p.0[1],
+            (p.0[2] as f64 * 1.02 + 3.0).min(255.0) as u8,
+            255,
+        ])
+    };
+
+    let img = test_bar(|x| Some(if x < 120 { 1 } else { 2 }));
+    let filtered = ImageBuffer::from_fn(200, 12, |x, y| filter(*img.get_pixel(x, y)));
+
+    let exact = BossHpBarMatcher::with_icon(
+        BOSS,
+        icon.clone(),
+        BossHpBarConfig {
+            tolerance: 0.0,
+            ..Default::default()
+        },
+    );
+    assert!(exact.find(&img).is_some());
+    assert!(exact.find(&filtered).is_none());
+
+    let matcher = BossHpBarMatcher::with_icon(BOSS, icon, BossHpBarConfig::default());
+    let result = matcher.find(&filtered).unwrap();
+    assert_eq!(result.layer(), 2);
+    assert_eq!(result.remaining_pixels, 100);
+    assert!(result.calibrated());
+    // The layer not on the screen is learned as well
+    let (top, _) = BOSS.layers[3];
+    let learned = matcher.layers.borrow()[3].0;
+    assert!(learned.delta_e(&Lab::from_bgra(filter(top))) < 2.0);
+
+    let result = matcher.find(&filtered).unwrap();
+    assert_eq!(result.layer(), 2);
+    assert_eq!(result.remaining_pixels, 100);
+}
+
+#[test]
+fn calibration_reset() {
+    const BOSS: BossDescriptor = TEST_BOSS;
+    let filter = |p: Bgra<u8>| {
+        Bgra([
+            (p.0[0] as f64 * 0.92) as u8,
+            p.0[1],
+            (p.0[2] as f64 * 1.02 + 3.0).min(255.0) as u8,
+            255,
+        ])
+    };
+    let img = test_bar(|x| Some(if x < 120 { 1 } else { 2 }));
+    let filtered = ImageBuffer::from_fn(200, 12, |x, y| filter(*img.get_pixel(x, y)));
+    let (top, _) = BOSS.layers[3];
+    let learned = |matcher: &BossHpBarMatcher| matcher.layers.borrow()[3].0;
+
+    let matcher = BossHpBarMatcher::with_icon(BOSS, test_icon(), BossHpBarConfig::default());
+    assert!(matcher.find(&filtered).unwrap().calibrated());
+    assert!(learned(&matcher).delta_e(&Lab::from_bgra(filter(top))) < 0.5);
+
+    // The next fight without the filter
+    matcher.calibration_reset().reset();
+    assert!(matcher.find(&img).unwrap().calibrated());
+    assert!(learned(&matcher).delta_e(&Lab::from_bgra(top)) < 0.5);
+}
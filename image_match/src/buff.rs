@@ -4,10 +4,54 @@ use image::{Bgra, GenericImageView, ImageBuffer, Pixel, SubImage};
 use once_cell::sync::OnceCell;
 use smallvec::SmallVec;
 
-use crate::{GenericImageViewExt, Matcher};
+use crate::{phash::IconIndex, GenericImageViewExt, Matcher};
+
+/// Score threshold of buff and debuff icons used by the timers.
+pub const DEFAULT_THRESHOLD: f64 = 0.8;
 
 static BUFF_EDGES: OnceCell<Vec<(u32, u32)>> = OnceCell::new();
 
+/// Top of the first row of buff icons.
+pub(crate) const BUFF_AREA_TOP: u32 = 3;
+
+/// 32x32 cells of the buff area that look like a buff icon.
+fn buff_cells<V: GenericImageView<Pixel = Bgra<u8>>>(
+    view: &V,
+) -> impl Iterator<Item = SubImage<&V::InnerImageView>> {
+    view.view(
+        (view.width() - 3) % 32,
+        BUFF_AREA_TOP,
+        view.width() - ((view.width() - 3) % 32),
+        (view.height() - BUFF_AREA_TOP).min(400),
+    )
+    .view_bounds_like((32, 32), 32)
+    .map(|(x, y, w, h)| view.view(x, y, w, h))
+    .chain(
+        view.view(
+            (view.width() - 3) % 32,
+            118,
+            view.width() - ((view.width() - 3) % 32),
+            (view.height() - 118).min(400),
+        )
+        .view_bounds_like((32, 32), 32)
+        .map(|(x, y, w, h)| view.view(x, y, w, h)),
+    )
+    .filter(|v| BuffMatcher::has_edges(v))
+}
+
+#[derive(Debug, Clone)]
+pub struct BuffMatchResult {
+    remaining_ratio: f64,
+}
+
+impl BuffMatchResult {
+    /// Estimated fraction of the buff duration remaining, read from the dark overlay that grows
+    /// over the icon as the buff expires.
+    pub fn remaining_ratio(&self) -> f64 {
+        self.remaining_ratio
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BuffMatcher {
     icon: ImageBuffer<Bgra<u8>, Vec<u8>>,
@@ -50,6 +94,65 @@ impl BuffMatcher {
         self.icon.get_pixel(x, y).channels4().3 == u8::MAX
     }
 
+    fn luminance(p: Bgra<u8>) -> f64 {
+        0.114 * p.0[0] as f64 + 0.587 * p.0[1] as f64 + 0.299 * p.0[2] as f64
+    }
+
+    /// Copy of the view with the expiration overlay undone, and the ratio of opaque icon pixels
+    /// which are not covered by the overlay.
+    ///
+    /// The overlay only darkens pixels, so the covered area is measured regardless of its shape.
+    /// Rows which are partly covered would no longer correlate with the icon, so covered pixels are
+    /// brightened back by the typical darkening of the overlay.
+    fn uncover<I>(&self, target: &SubImage<&I>) -> (ImageBuffer<Bgra<u8>, Vec<u8>>, f64)
+    where
+        I: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        const MIN_LUMINANCE: f64 = 40.0;
+        const DARKEN_RATIO: f64 = 0.7;
+        // Darker than this is not the overlay, which leaves the icon visible
+        const MIN_OVERLAY_RATIO: f64 = 0.2;
+
+        let (w, h) = self.icon.dimensions();
+        let mut total = 0;
+        let mut covered = vec![false; (w * h) as usize];
+        let mut ratios = Vec::new();
+        for (x, y, p) in self.icon.enumerate_pixels() {
+            if !self.is_opaque(x, y) || Self::luminance(*p) < MIN_LUMINANCE {
+                continue;
+            }
+            total += 1;
+            let ratio = Self::luminance(target.get_pixel(x, y)) / Self::luminance(*p);
+            if ratio < DARKEN_RATIO {
+                covered[(y * w + x) as usize] = true;
+                ratios.push(ratio);
+            }
+        }
+        let remaining_ratio = if total == 0 {
+            1.0
+        } else {
+            1.0 - ratios.len() as f64 / total as f64
+        };
+
+        ratios.sort_by(f64::total_cmp);
+        let overlay = ratios
+            .get(ratios.len() / 2)
+            .copied()
+            .filter(|&x| x >= MIN_OVERLAY_RATIO);
+        let uncovered = ImageBuffer::from_fn(w, h, |x, y| {
+            let p = target.get_pixel(x, y);
+            match overlay {
+                Some(overlay) if covered[(y * w + x) as usize] => {
+                    let [b, g, r, a] = p.0;
+                    let undo = |c: u8| (c as f64 / overlay).min(255.0) as u8;
+                    Bgra([undo(b), undo(g), undo(r), a])
+                }
+                _ => p,
+            }
+        });
+        (uncovered, remaining_ratio)
+    }
+
     fn ncc(x: &[f64], y: &[f64]) -> f64 {
         let n = x.len() as f64;
         let x_mean = x.iter().sum::<f64>() / n;
@@ -89,28 +192,46 @@ impl BuffMatcher {
             Self::ncc(&icon_b, &target_b),
         )
     }
+
+    /// The highest threshold which the view would match with. Slower than matching, since every
+    /// row is compared.
+    pub fn score<I>(&self, target: &SubImage<&I>) -> f64
+    where
+        I: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        let (w, h) = self.icon.dimensions();
+        let (uncovered, _) = self.uncover(target);
+        let target = uncovered.view(0, 0, w, h);
+        let mut scores = (0..h)
+            .map(|y| {
+                let (r, g, b) = self.row_ncc(&target, y);
+                r.min(g).min(b)
+            })
+            .collect::<Vec<_>>();
+        scores.sort_by(f64::total_cmp);
+        // Matching allows less than a third of the rows to fail
+        scores[(h / 3).saturating_sub(1) as usize]
+    }
 }
 
 impl<V: GenericImageView<Pixel = Bgra<u8>>> Matcher<V> for BuffMatcher {
-    type MatchResult = ();
+    type MatchResult = BuffMatchResult;
 
     type CandidatesIter<'a> = impl Iterator<Item = SubImage<&'a V::InnerImageView>> + 'a where V: 'a;
 
     fn init() {
-        let im = assets()
-            .load::<Png>("buff_edge")
-            .unwrap()
-            .cloned()
-            .0
-            .to_bgra8();
-        BUFF_EDGES
-            .set(
-                im.enumerate_pixels()
-                    .filter(|(_, _, p)| p.0[3] == 255)
-                    .map(|(x, y, _)| (x, y))
-                    .collect::<Vec<_>>(),
-            )
-            .unwrap();
+        BUFF_EDGES.get_or_init(|| {
+            let im = assets()
+                .load::<Png>("buff_edge")
+                .unwrap()
+                .cloned()
+                .0
+                .to_bgra8();
+            im.enumerate_pixels()
+                .filter(|(_, _, p)| p.0[3] == 255)
+                .map(|(x, y, _)| (x, y))
+                .collect::<Vec<_>>()
+        });
     }
 
     fn view_dimensions(&self) -> (u32, u32) {
@@ -121,32 +242,16 @@ impl<V: GenericImageView<Pixel = Bgra<u8>>> Matcher<V> for BuffMatcher {
     where
         V: GenericImageView<Pixel = Bgra<u8>>,
     {
-        view.view(
-            (view.width() - 3) % 32,
-            3,
-            view.width() - ((view.width() - 3) % 32),
-            (view.height() - 3).min(400),
-        )
-        .view_bounds_like((32, 32), 32)
-        .map(|(x, y, w, h)| view.view(x, y, w, h))
-        .chain(
-            view.view(
-                (view.width() - 3) % 32,
-                118,
-                view.width() - ((view.width() - 3) % 32),
-                (view.height() - 118).min(400),
-            )
-            .view_bounds_like((32, 32), 32)
-            .map(|(x, y, w, h)| view.view(x, y, w, h)),
-        )
-        .filter(|v| Self::has_edges(v))
+        buff_cells(view)
     }
 
     fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
-        let h = self.icon.height();
+        let (w, h) = self.icon.dimensions();
+        let (uncovered, remaining_ratio) = self.uncover(view);
+        let target = uncovered.view(0, 0, w, h);
         let mut fail = 0;
         for y in 0..h {
-            let (r, g, b) = self.row_ncc(view, y);
+            let (r, g, b) = self.row_ncc(&target, y);
             if r < self.threshold || g < self.threshold || b < self.threshold {
                 fail += 1;
                 if fail >= h / 3 {
@@ -155,10 +260,226 @@ impl<V: GenericImageView<Pixel = Bgra<u8>>> Matcher<V> for BuffMatcher {
             }
         }
 
-        Some(())
+        Some(BuffMatchResult { remaining_ratio })
     }
 
     fn check<'a>(&self, view: &SubImage<&'a V>) -> bool {
         self.match_image(view).is_some()
     }
 }
+
+/// A buff found by [`BuffScanner`].
+#[derive(Debug, Clone)]
+pub struct ActiveBuff<K> {
+    pub kind: K,
+    /// Column of the buff icon, counted from the right end of the buff area.
+    pub slot: u32,
+    pub bounds: (u32, u32, u32, u32),
+    pub result: BuffMatchResult,
+}
+
+/// Finds every buff in a catalog of icons with a single walk over the buff area.
+///
+/// Unlike [`BuffMatcher`], the match result is a snapshot of all active buffs on the screen.
+#[derive(Debug, Clone)]
+pub struct BuffScanner<K> {
+    catalog: Vec<(K, BuffMatcher)>,
+    /// Index of the catalog, and how many icons to verify for each cell.
+    index: Option<(IconIndex<usize>, usize)>,
+}
+
+impl<K> BuffScanner<K> {
+    pub fn new(
+        catalog: impl IntoIterator<Item = (K, ImageBuffer<Bgra<u8>, Vec<u8>>)>,
+        threshold: f64,
+        dims: (u32, u32),
+    ) -> Self {
+        Self {
+            catalog: catalog
+                .into_iter()
+                .map(|(kind, icon)| (kind, BuffMatcher::new(icon, threshold, dims)))
+                .collect(),
+            index: None,
+        }
+    }
+
+    /// Only verifies the `k` icons of the catalog nearest to each cell by perceptual hash, instead
+    /// of every icon. For large catalogs, where comparing every icon is too slow.
+    pub fn with_index(mut self, k: usize) -> Self {
+        let icons = self
+            .catalog
+            .iter()
+            .enumerate()
+            .map(|(i, (_, matcher))| (i, matcher.icon.clone()));
+        self.index = IconIndex::new(icons).map(|index| (index, k));
+        self
+    }
+
+    /// The first icon of the catalog which matches the cell.
+    pub(crate) fn classify<I>(&self, cell: &SubImage<&I>) -> Option<(&K, BuffMatchResult)>
+    where
+        I: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        let verify = |i: usize| {
+            let (kind, matcher) = &self.catalog[i];
+            Some((kind, Matcher::<I>::match_image(matcher, cell)?))
+        };
+        match &self.index {
+            Some((index, k)) => index
+                .nearest(cell, *k)
+                .into_iter()
+                .find_map(|(_, &i)| verify(i)),
+            None => (0..self.catalog.len()).find_map(verify),
+        }
+    }
+}
+
+impl<K, V> Matcher<V> for BuffScanner<K>
+where
+    K: Clone + std::fmt::Debug,
+    V: GenericImageView<Pixel = Bgra<u8>> + GenericImageView<InnerImageView = V>,
+{
+    type MatchResult = Vec<ActiveBuff<K>>;
+
+    type CandidatesIter<'a> = std::iter::Once<SubImage<&'a V::InnerImageView>> where V: 'a;
+
+    fn init() {
+        <BuffMatcher as Matcher<V>>::init();
+    }
+
+    fn view_dimensions(&self) -> (u32, u32) {
+        self.catalog
+            .first()
+            .map(|(_, matcher)| matcher.dims)
+            .unwrap_or_default()
+    }
+
+    fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a>
+    where
+        V: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        std::iter::once(view.view(0, 0, view.width(), view.height()))
+    }
+
+    fn check<'a>(&self, _view: &SubImage<&'a V>) -> bool {
+        true
+    }
+
+    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        let screen = view.inner();
+        let right = screen.width();
+        Some(
+            buff_cells(screen)
+                .filter_map(|cell| {
+                    let (kind, result) = self.classify(&cell)?;
+                    let bounds = cell.bounds();
+                    Some(ActiveBuff {
+                        kind: kind.clone(),
+                        slot: (right - bounds.0) / 32 - 1,
+                        bounds,
+                        result,
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+
+#[test]
+fn buff_scanner_finds_icons_in_slots() {
+    // Edges of the cell, as outlined by the frame of buff icons
+    BUFF_EDGES.get_or_init(|| {
+        (0..32)
+            .flat_map(|i| [(i, 0), (i, 31), (0, i), (31, i)])
+            .collect()
+    });
+    let icon = |flip: bool| {
+        ImageBuffer::from_fn(32, 32, |x, y| {
+            if x == 0 || y == 0 || x == 31 || y == 31 {
+                return Bgra([0, 0, 0, 255]);
+            }
+            let x = if flip { 31 - x } else { x };
+            let v = (x * 8) as u8;
+            Bgra([v, (v / 2).wrapping_add(y as u8 * 4), 255 - v, 255])
+        })
+    };
+    let scanner = BuffScanner::new(
+        [("fatal_strike", icon(false)), ("holy_symbol", icon(true))],
+        0.8,
+        (1366, 768),
+    );
+
+    // The columns of the buff area start at (1366 - 3) % 32 = 19, and the rows at 3
+    let mut screen = ImageBuffer::from_pixel(1366, 768, Bgra([0, 0, 0, 255]));
+    let right = 19 + 32 * 41;
+    image::imageops::replace(&mut screen, &icon(false), right, 3);
+    image::imageops::replace(&mut screen, &icon(true), right - 32 * 2, 3 + 32);
+
+    let mut found = scanner.find(&screen).unwrap();
+    found.sort_by_key(|x| x.slot);
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].kind, "fatal_strike");
+    assert_eq!(found[0].slot, 0);
+    assert_eq!(found[0].bounds, (right, 3, 32, 32));
+    assert_eq!(found[1].kind, "holy_symbol");
+    assert_eq!(found[1].slot, 2);
+    assert_eq!(found[1].bounds, (right - 64, 35, 32, 32));
+    assert!(found.iter().all(|x| x.result.remaining_ratio() > 0.95));
+}
+
+/// Darkens the part of the icon swept clockwise from 12 o'clock by the expiration overlay.
+#[cfg(test)]
+fn with_overlay(
+    icon: &ImageBuffer<Bgra<u8>, Vec<u8>>,
+    elapsed: f64,
+) -> ImageBuffer<Bgra<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(32, 32, |x, y| {
+        let (dx, dy) = (x as f64 - 15.5, y as f64 - 15.5);
+        let angle = dx.atan2(-dy).rem_euclid(std::f64::consts::TAU) / std::f64::consts::TAU;
+        let p = icon.get_pixel(x, y).0;
+        if angle < elapsed {
+            let dark = |c: u8| (c as f64 * 0.4) as u8;
+            Bgra([dark(p[0]), dark(p[1]), dark(p[2]), 255])
+        } else {
+            Bgra(p)
+        }
+    })
+}
+
+#[test]
+fn buff_with_partial_overlay() {
+    let icon = ImageBuffer::from_fn(32, 32, |x, y| {
+        Bgra([
+            (x * 7) as u8,
+            (x * 5 + y * 3) as u8,
+            (x * x / 4 + 40) as u8,
+            255,
+        ])
+    });
+    let matcher = BuffMatcher::new(icon.clone(), 0.8, (32, 32));
+    for elapsed in [0.3, 0.6, 0.9] {
+        let target = with_overlay(&icon, elapsed);
+        let result = Matcher::<ImageBuffer<Bgra<u8>, Vec<u8>>>::match_image(
+            &matcher,
+            &target.view(0, 0, 32, 32),
+        );
+        let ratio = result.expect("covered icon should match").remaining_ratio();
+        assert!(
+            (ratio - (1.0 - elapsed)).abs() < 0.1,
+            "{} {}",
+            elapsed,
+            ratio
+        );
+    }
+
+    // Neither a dark cell nor another icon under the overlay matches
+    let black = ImageBuffer::from_pixel(32, 32, Bgra([0, 0, 0, 255]));
+    let other = with_overlay(&image::imageops::flip_horizontal(&icon), 0.6);
+    for target in [black, other] {
+        let result = Matcher::<ImageBuffer<Bgra<u8>, Vec<u8>>>::match_image(
+            &matcher,
+            &target.view(0, 0, 32, 32),
+        );
+        assert!(result.is_none());
+    }
+}
@@ -0,0 +1,94 @@
+//! Perceptual color difference, for matching colors which went through filters of the screen,
+//! e.g. night light, gamma adjustment or HDR tone mapping.
+
+use image::Bgra;
+
+/// CIELAB coordinates of a sRGB color, under the D65 white point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lab(pub [f64; 3]);
+
+impl Lab {
+    pub fn from_bgra(p: Bgra<u8>) -> Self {
+        let linear = |c: u8| {
+            let c = c as f64 / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        let [b, g, r, _] = p.0;
+        let (r, g, b) = (linear(r), linear(g), linear(b));
+        let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+        let f = |t: f64| {
+            if t > 216.0 / 24389.0 {
+                t.cbrt()
+            } else {
+                (24389.0 / 27.0 * t + 16.0) / 116.0
+            }
+        };
+        let (fx, fy, fz) = (f(x), f(y), f(z));
+        Lab([116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)])
+    }
+
+    /// CIE76 color difference. Differences below 2 are hardly noticeable.
+    pub fn delta_e(&self, other: &Lab) -> f64 {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum::<f64>()
+            .sqrt()
+    }
+}
+
+/// CIE76 color difference of the two colors, ignoring alpha.
+pub fn delta_e(a: Bgra<u8>, b: Bgra<u8>) -> f64 {
+    Lab::from_bgra(a).delta_e(&Lab::from_bgra(b))
+}
+
+/// Per-channel linear map of colors, e.g. from the reference colors to the colors on the screen of
+/// the user.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorTransform {
+    /// `(gain, offset)` of blue, green and red.
+    channels: [(f64, f64); 3],
+}
+
+impl ColorTransform {
+    /// Least squares fit of the pairs of reference and observed colors. A channel with a single
+    /// distinct reference value only gets an offset.
+    pub fn fit(samples: &[(Bgra<u8>, Bgra<u8>)]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let n = samples.len() as f64;
+        let mut channels = [(1.0, 0.0); 3];
+        for (c, channel) in channels.iter_mut().enumerate() {
+            let mean_x = samples.iter().map(|(x, _)| x.0[c] as f64).sum::<f64>() / n;
+            let mean_y = samples.iter().map(|(_, y)| y.0[c] as f64).sum::<f64>() / n;
+            let var = samples
+                .iter()
+                .map(|(x, _)| (x.0[c] as f64 - mean_x).powi(2))
+                .sum::<f64>();
+            let cov = samples
+                .iter()
+                .map(|(x, y)| (x.0[c] as f64 - mean_x) * (y.0[c] as f64 - mean_y))
+                .sum::<f64>();
+            let gain = if var < 1.0 { 1.0 } else { cov / var };
+            *channel = (gain, mean_y - gain * mean_x);
+        }
+        Some(Self { channels })
+    }
+
+    pub fn apply(&self, p: Bgra<u8>) -> Bgra<u8> {
+        let mut ret = p;
+        for (c, (gain, offset)) in self.channels.iter().enumerate() {
+            ret.0[c] = (p.0[c] as f64 * gain + offset).round().clamp(0.0, 255.0) as u8;
+        }
+        ret
+    }
+}
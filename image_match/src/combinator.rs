@@ -0,0 +1,270 @@
+use std::fmt::Debug;
+
+use image::{Bgra, GenericImageView, SubImage};
+
+use crate::Matcher;
+
+/// Tries each inner matcher over the whole screen, and returns the result of the first one that hits.
+///
+/// Supported inner types are tuples of matchers sharing the same `MatchResult` and `Vec`s of a matcher.
+/// Use [`Map`] to unify result types beforehand if needed.
+pub struct AnyOf<T>(pub T);
+
+/// Succeeds only when every inner matcher hits on the same screen. Returns a tuple of the results.
+pub struct AllOf<T>(pub T);
+
+/// Runs the (expensive) second matcher only when the (cheap) first matcher hits on the screen.
+///
+/// For example, `Gate(BossHpBarMatcher::new(JIN_HILLAH)?, JinHillahReapMatcher::new(dims))` looks
+/// for the reap animation only while the Jin Hillah HP bar is visible.
+pub struct Gate<C, E>(pub C, pub E);
+
+/// Transforms results of the inner matcher with a function.
+pub struct Map<M, F>(pub M, pub F);
+
+macro_rules! whole_screen_matcher {
+    () => {
+        type CandidatesIter<'a> = std::iter::Once<SubImage<&'a V::InnerImageView>> where V: 'a;
+
+        /// Combinators scan the whole screen by themselves, so this is only a hint.
+        fn view_dimensions(&self) -> (u32, u32) {
+            (0, 0)
+        }
+
+        fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a>
+        where
+            V: GenericImageView<Pixel = Bgra<u8>>,
+        {
+            std::iter::once(view.view(0, 0, view.width(), view.height()))
+        }
+
+        fn check<'a>(&self, _view: &SubImage<&'a V>) -> bool {
+            true
+        }
+    };
+}
+
+macro_rules! impl_tuple_combinators {
+    ($($name:ident $idx:tt),+) => {
+        impl<V, A, $($name),+> Matcher<V> for AnyOf<(A, $($name),+)>
+        where
+            V: GenericImageView<Pixel = Bgra<u8>> + GenericImageView<InnerImageView = V>,
+            A: Matcher<V>,
+            $($name: Matcher<V, MatchResult = A::MatchResult>),+
+        {
+            type MatchResult = A::MatchResult;
+
+            whole_screen_matcher!();
+
+            fn init() {
+                A::init();
+                $($name::init();)+
+            }
+
+            fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+                let screen = view.inner();
+                self.0 .0.find(screen)$(.or_else(|| self.0.$idx.find(screen)))+
+            }
+        }
+
+        impl<V, A, $($name),+> Matcher<V> for AllOf<(A, $($name),+)>
+        where
+            V: GenericImageView<Pixel = Bgra<u8>> + GenericImageView<InnerImageView = V>,
+            A: Matcher<V>,
+            $($name: Matcher<V>),+
+        {
+            type MatchResult = (A::MatchResult, $($name::MatchResult),+);
+
+            whole_screen_matcher!();
+
+            fn init() {
+                A::init();
+                $($name::init();)+
+            }
+
+            fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+                let screen = view.inner();
+                Some((self.0 .0.find(screen)?, $(self.0.$idx.find(screen)?),+))
+            }
+        }
+    };
+}
+
+impl_tuple_combinators!(B 1);
+impl_tuple_combinators!(B 1, C 2);
+impl_tuple_combinators!(B 1, C 2, D 3);
+
+impl<V, M> Matcher<V> for AnyOf<Vec<M>>
+where
+    V: GenericImageView<Pixel = Bgra<u8>> + GenericImageView<InnerImageView = V>,
+    M: Matcher<V>,
+{
+    type MatchResult = M::MatchResult;
+
+    whole_screen_matcher!();
+
+    fn init() {
+        M::init();
+    }
+
+    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        let screen = view.inner();
+        self.0.iter().find_map(|m| m.find(screen))
+    }
+}
+
+impl<V, M> Matcher<V> for AllOf<Vec<M>>
+where
+    V: GenericImageView<Pixel = Bgra<u8>> + GenericImageView<InnerImageView = V>,
+    M: Matcher<V>,
+{
+    type MatchResult = Vec<M::MatchResult>;
+
+    whole_screen_matcher!();
+
+    fn init() {
+        M::init();
+    }
+
+    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        let screen = view.inner();
+        self.0.iter().map(|m| m.find(screen)).collect()
+    }
+}
+
+impl<V, C, E> Matcher<V> for Gate<C, E>
+where
+    V: GenericImageView<Pixel = Bgra<u8>> + GenericImageView<InnerImageView = V>,
+    C: Matcher<V>,
+    E: Matcher<V>,
+{
+    type MatchResult = E::MatchResult;
+
+    type CandidatesIter<'a> = std::iter::Flatten<std::option::IntoIter<E::CandidatesIter<'a>>>
+        where V: 'a;
+
+    fn init() {
+        C::init();
+        E::init();
+    }
+
+    fn view_dimensions(&self) -> (u32, u32) {
+        self.1.view_dimensions()
+    }
+
+    fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a>
+    where
+        V: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        self.0
+            .find(view)
+            .map(|_| self.1.candidates_iter(view))
+            .into_iter()
+            .flatten()
+    }
+
+    fn check<'a>(&self, view: &SubImage<&'a V>) -> bool {
+        self.1.check(view)
+    }
+
+    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        self.1.match_image(view)
+    }
+}
+
+impl<V, M, F, R> Matcher<V> for Map<M, F>
+where
+    V: GenericImageView<Pixel = Bgra<u8>>,
+    M: Matcher<V>,
+    F: Fn(M::MatchResult) -> R,
+    R: Debug,
+{
+    type MatchResult = R;
+
+    type CandidatesIter<'a> = M::CandidatesIter<'a> where V: 'a;
+
+    fn init() {
+        M::init();
+    }
+
+    fn view_dimensions(&self) -> (u32, u32) {
+        self.0.view_dimensions()
+    }
+
+    fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a>
+    where
+        V: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        self.0.candidates_iter(view)
+    }
+
+    fn check<'a>(&self, view: &SubImage<&'a V>) -> bool {
+        self.0.check(view)
+    }
+
+    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        self.0.match_image(view).map(&self.1)
+    }
+}
+
+#[cfg(test)]
+#[derive(Clone)]
+pub(crate) struct PixelMatcher(pub Bgra<u8>);
+
+#[cfg(test)]
+impl<V: GenericImageView<Pixel = Bgra<u8>>> Matcher<V> for PixelMatcher {
+    type MatchResult = (u32, u32);
+    type CandidatesIter<'a> = std::vec::IntoIter<SubImage<&'a V::InnerImageView>> where V: 'a;
+
+    fn view_dimensions(&self) -> (u32, u32) {
+        (1, 1)
+    }
+
+    fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a> {
+        let (w, h) = view.dimensions();
+        (0..h)
+            .flat_map(|y| (0..w).map(move |x| view.view(x, y, 1, 1)))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        (view.get_pixel(0, 0) == self.0).then(|| (view.bounds().0, view.bounds().1))
+    }
+}
+
+#[test]
+fn combinators() {
+    let red = Bgra([0, 0, 255, 255]);
+    let green = Bgra([0, 255, 0, 255]);
+    let blue = Bgra([255, 0, 0, 255]);
+    let mut img = image::ImageBuffer::from_pixel(4, 4, Bgra([0, 0, 0, 255]));
+    img.put_pixel(1, 2, red);
+    img.put_pixel(3, 0, green);
+
+    assert_eq!(
+        AnyOf((PixelMatcher(blue), PixelMatcher(green))).find(&img),
+        Some((3, 0))
+    );
+    assert_eq!(
+        AnyOf(vec![PixelMatcher(blue), PixelMatcher(red)]).find(&img),
+        Some((1, 2))
+    );
+    assert_eq!(
+        AllOf((PixelMatcher(red), PixelMatcher(green))).find(&img),
+        Some(((1, 2), (3, 0)))
+    );
+    assert_eq!(
+        AllOf((PixelMatcher(red), PixelMatcher(blue))).find(&img),
+        None
+    );
+    assert_eq!(
+        Gate(PixelMatcher(red), PixelMatcher(green)).find(&img),
+        Some((3, 0))
+    );
+    assert_eq!(
+        Gate(PixelMatcher(blue), PixelMatcher(green)).find(&img),
+        None
+    );
+    assert_eq!(Map(PixelMatcher(red), |(x, y)| x + y).find(&img), Some(3));
+}
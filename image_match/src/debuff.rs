@@ -0,0 +1,142 @@
+//! Debuffs of the player, e.g. stun or seal, whose icons are shown in their own area below the
+//! buffs.
+
+use image::{Bgra, GenericImageView, ImageBuffer, SubImage};
+
+use crate::{
+    buff::{ActiveBuff, BuffMatcher, BuffScanner, BUFF_AREA_TOP},
+    GenericImageViewExt, Matcher,
+};
+
+/// Rows of buff icons above the debuff area.
+const BUFF_ROWS: u32 = 2;
+/// Rows of debuff icons.
+const DEBUFF_ROWS: u32 = 2;
+/// Top of the debuff area, right below the rows of buff icons at 3 and 35. Its icons are aligned
+/// to the right end of the screen, like buffs.
+const DEBUFF_AREA_TOP: u32 = BUFF_AREA_TOP + 32 * BUFF_ROWS;
+const DEBUFF_AREA_HEIGHT: u32 = 32 * DEBUFF_ROWS;
+
+/// 32x32 cells of the debuff area.
+///
+/// Debuff icons have no frame like buff icons do, so every cell is a candidate. There is no cell if
+/// the screen is too small for the debuff area.
+fn debuff_cells<V: GenericImageView<Pixel = Bgra<u8>>>(
+    view: &V,
+) -> impl Iterator<Item = SubImage<&V::InnerImageView>> {
+    let fits = view.width() >= 32 + 3 && view.height() >= DEBUFF_AREA_TOP + 32;
+    fits.then(|| {
+        view.view(
+            (view.width() - 3) % 32,
+            DEBUFF_AREA_TOP,
+            view.width() - ((view.width() - 3) % 32),
+            (view.height() - DEBUFF_AREA_TOP).min(DEBUFF_AREA_HEIGHT),
+        )
+        .view_bounds_like((32, 32), 32)
+        .map(|(x, y, w, h)| view.view(x, y, w, h))
+    })
+    .into_iter()
+    .flatten()
+}
+
+/// Finds every debuff in a catalog of icons with a single walk over the debuff area.
+///
+/// Icons are compared the same way as by [`BuffScanner`], so the results also tell how much of the
+/// debuff duration remains.
+#[derive(Debug, Clone)]
+pub struct DebuffScanner<K>(BuffScanner<K>);
+
+impl<K> DebuffScanner<K> {
+    pub fn new(
+        catalog: impl IntoIterator<Item = (K, ImageBuffer<Bgra<u8>, Vec<u8>>)>,
+        threshold: f64,
+        dims: (u32, u32),
+    ) -> Self {
+        Self(BuffScanner::new(catalog, threshold, dims))
+    }
+
+    /// See [`BuffScanner::with_index`].
+    pub fn with_index(self, k: usize) -> Self {
+        Self(self.0.with_index(k))
+    }
+}
+
+impl<K, V> Matcher<V> for DebuffScanner<K>
+where
+    K: Clone + std::fmt::Debug,
+    V: GenericImageView<Pixel = Bgra<u8>> + GenericImageView<InnerImageView = V>,
+{
+    type MatchResult = Vec<ActiveBuff<K>>;
+
+    type CandidatesIter<'a> = std::iter::Once<SubImage<&'a V::InnerImageView>> where V: 'a;
+
+    fn init() {
+        <BuffMatcher as Matcher<V>>::init();
+    }
+
+    fn view_dimensions(&self) -> (u32, u32) {
+        Matcher::<V>::view_dimensions(&self.0)
+    }
+
+    fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a>
+    where
+        V: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        std::iter::once(view.view(0, 0, view.width(), view.height()))
+    }
+
+    fn check<'a>(&self, _view: &SubImage<&'a V>) -> bool {
+        true
+    }
+
+    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        let screen = view.inner();
+        let right = screen.width();
+        Some(
+            debuff_cells(screen)
+                .filter_map(|cell| {
+                    let (kind, result) = self.0.classify(&cell)?;
+                    let bounds = cell.bounds();
+                    Some(ActiveBuff {
+                        kind: kind.clone(),
+                        slot: (right - bounds.0) / 32 - 1,
+                        bounds,
+                        result,
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+
+#[test]
+fn debuff_scanner_finds_icons() {
+    let icon = |flip: bool| {
+        ImageBuffer::from_fn(32, 32, |x, y| {
+            let x = if flip { 31 - x } else { x };
+            let v = (x * 8) as u8;
+            Bgra([v, (v / 2).wrapping_add(y as u8 * 4), 255 - v, 255])
+        })
+    };
+    let scanner = DebuffScanner::new(
+        [("stun", icon(false)), ("seal", icon(true))],
+        0.8,
+        (1366, 768),
+    );
+
+    // The columns of the debuff area start at (1366 - 3) % 32 = 19
+    let mut screen = ImageBuffer::from_pixel(1366, 768, Bgra([0, 0, 0, 255]));
+    let (x, y) = (19 + 32 * 40, DEBUFF_AREA_TOP + 32);
+    image::imageops::replace(&mut screen, &icon(true), x, y);
+
+    assert_eq!(DEBUFF_AREA_TOP, 67);
+    let found = scanner.find(&screen).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].kind, "seal");
+    assert_eq!(found[0].slot, 1);
+    assert_eq!(found[0].bounds, (x, y, 32, 32));
+
+    // A window too small for the debuff area
+    let small = ImageBuffer::from_pixel(300, DEBUFF_AREA_TOP + 20, Bgra([0, 0, 0, 255]));
+    assert_eq!(scanner.find(&small).unwrap().len(), 0);
+}
@@ -0,0 +1,245 @@
+//! Recording how a matcher handled each candidate of a screen, and drawing it on the screen.
+//!
+//! [`trace`] runs a matcher like [`Matcher::find`], but keeps going after the first match and
+//! returns a [`DebugReport`] along with the result. [`DebugReport::annotate`] outlines each
+//! candidate in the color of its [`Outcome`]: grey if rejected by [`Matcher::check`], yellow if
+//! checked but not matched, and green if matched with the score above it. Cached bounds are
+//! outlined in magenta.
+
+use image::{Bgra, GenericImageView, ImageBuffer, SubImage};
+
+use crate::Matcher;
+
+/// What the matcher did with a candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// [`Matcher::check`] returned false.
+    Rejected,
+    /// Passed [`Matcher::check`], but [`Matcher::match_image`] returned `None`.
+    Checked,
+    Matched,
+}
+
+impl Outcome {
+    fn color(self) -> Bgra<u8> {
+        match self {
+            Outcome::Rejected => Bgra([128, 128, 128, 255]),
+            Outcome::Checked => Bgra([0, 230, 255, 255]),
+            Outcome::Matched => Bgra([0, 230, 0, 255]),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DebugCandidate {
+    /// `(x, y, width, height)` of the candidate on the screen.
+    pub bounds: (u32, u32, u32, u32),
+    pub outcome: Outcome,
+    /// `Debug` representation of the match result.
+    pub result: Option<String>,
+    pub score: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DebugReport {
+    pub candidates: Vec<DebugCandidate>,
+    /// Bounds cached before the screen, see [`Matcher::cached_bounds`].
+    pub cached_bounds: Option<(u32, u32, u32, u32)>,
+}
+
+/// Runs the matcher over all candidates of the screen, and returns the first match result with
+/// the report.
+///
+/// Matching may change the state of the matcher, e.g. the bounds cached by
+/// [`crate::BoundsCachedMatcher`], so a clone of the matcher is traced. Run [`Matcher::find`] on
+/// the matcher itself to update its state as without tracing.
+pub fn trace<M, V>(matcher: &M, view: &V) -> (Option<M::MatchResult>, DebugReport)
+where
+    M: Matcher<V> + Clone,
+    V: GenericImageView<Pixel = Bgra<u8>> + GenericImageView<InnerImageView = V>,
+{
+    trace_with_score(matcher, view, |_, _| None)
+}
+
+/// Same as [`trace`], with scores of the matched candidates shown on the annotated image, e.g. a
+/// correlation.
+pub fn trace_with_score<M, V>(
+    matcher: &M,
+    view: &V,
+    score: impl Fn(&SubImage<&V>, &M::MatchResult) -> Option<f64>,
+) -> (Option<M::MatchResult>, DebugReport)
+where
+    M: Matcher<V> + Clone,
+    V: GenericImageView<Pixel = Bgra<u8>> + GenericImageView<InnerImageView = V>,
+{
+    let cached_bounds = matcher.cached_bounds();
+    let matcher = matcher.clone();
+    let mut first = None;
+    let mut candidates = Vec::new();
+    for candidate in matcher.candidates_iter(view) {
+        let bounds = candidate.bounds();
+        let result = if matcher.check(&candidate) {
+            matcher.match_image(&candidate)
+        } else {
+            candidates.push(DebugCandidate {
+                bounds,
+                outcome: Outcome::Rejected,
+                result: None,
+                score: None,
+            });
+            continue;
+        };
+
+        candidates.push(match &result {
+            Some(result) => DebugCandidate {
+                bounds,
+                outcome: Outcome::Matched,
+                result: Some(format!("{:?}", result)),
+                score: score(&candidate, result),
+            },
+            None => DebugCandidate {
+                bounds,
+                outcome: Outcome::Checked,
+                result: None,
+                score: None,
+            },
+        });
+        if first.is_none() {
+            first = result;
+        }
+    }
+
+    let report = DebugReport {
+        candidates,
+        cached_bounds,
+    };
+    (first, report)
+}
+
+impl DebugReport {
+    pub fn first_match(&self) -> Option<&DebugCandidate> {
+        self.candidates
+            .iter()
+            .find(|x| x.outcome == Outcome::Matched)
+    }
+
+    /// Draws the candidates on the screen the report was made from.
+    pub fn annotate(&self, image: &mut ImageBuffer<Bgra<u8>, Vec<u8>>) {
+        // Draw rejected ones first, as candidates often overlap
+        let mut candidates = self.candidates.iter().collect::<Vec<_>>();
+        candidates.sort_by_key(|x| x.outcome != Outcome::Rejected);
+        for candidate in candidates {
+            draw_rect(image, candidate.bounds, 0, candidate.outcome.color());
+            if let Some(score) = candidate.score {
+                let (x, y, _, _) = candidate.bounds;
+                let text = format!("{:.3}", score);
+                draw_text(
+                    image,
+                    (x, y.saturating_sub(GLYPH_HEIGHT + 2)),
+                    &text,
+                    candidate.outcome.color(),
+                );
+            }
+        }
+        if let Some(bounds) = self.cached_bounds {
+            draw_rect(image, bounds, 2, Bgra([255, 0, 255, 255]));
+        }
+    }
+}
+
+/// Outlines the bounds, `margin` pixels outside of it.
+fn draw_rect(
+    image: &mut ImageBuffer<Bgra<u8>, Vec<u8>>,
+    (x, y, w, h): (u32, u32, u32, u32),
+    margin: u32,
+    color: Bgra<u8>,
+) {
+    let (x0, y0) = (x.saturating_sub(margin), y.saturating_sub(margin));
+    let (x1, y1) = (
+        (x + w + margin).min(image.width()),
+        (y + h + margin).min(image.height()),
+    );
+    if x0 >= x1 || y0 >= y1 {
+        return;
+    }
+    for x in x0..x1 {
+        image.put_pixel(x, y0, color);
+        image.put_pixel(x, y1 - 1, color);
+    }
+    for y in y0..y1 {
+        image.put_pixel(x0, y, color);
+        image.put_pixel(x1 - 1, y, color);
+    }
+}
+
+const GLYPH_HEIGHT: u32 = 5;
+
+/// 3x5 glyphs of `0123456789.-`, with the bit `3 * y + x` set for a pixel at `(x, y)`.
+const GLYPHS: [u16; 12] = [
+    0b111_101_101_101_111,
+    0b111_010_010_011_010,
+    0b111_001_111_100_111,
+    0b111_100_111_100_111,
+    0b100_100_111_101_101,
+    0b111_100_111_001_111,
+    0b111_101_111_001_111,
+    0b100_100_100_100_111,
+    0b111_101_111_101_111,
+    0b111_100_111_101_111,
+    0b010_000_000_000_000,
+    0b000_000_111_000_000,
+];
+
+/// Draws the number text at `(x, y)`, skipping characters without a glyph.
+fn draw_text(
+    image: &mut ImageBuffer<Bgra<u8>, Vec<u8>>,
+    (x, y): (u32, u32),
+    text: &str,
+    color: Bgra<u8>,
+) {
+    let glyphs = text.chars().filter_map(|c| match c {
+        '0'..='9' => Some(GLYPHS[c as usize - '0' as usize]),
+        '.' => Some(GLYPHS[10]),
+        '-' => Some(GLYPHS[11]),
+        _ => None,
+    });
+    for (i, glyph) in glyphs.enumerate() {
+        for (gx, gy) in (0..GLYPH_HEIGHT).flat_map(|gy| (0..3).map(move |gx| (gx, gy))) {
+            let (px, py) = (x + 4 * i as u32 + gx, y + gy);
+            if glyph & 1 << (3 * gy + gx) != 0 && px < image.width() && py < image.height() {
+                image.put_pixel(px, py, color);
+            }
+        }
+    }
+}
+
+#[test]
+fn trace_outcomes() {
+    let red = Bgra([0, 0, 255, 255]);
+    let mut img = ImageBuffer::from_pixel(6, 6, Bgra([0, 0, 0, 255]));
+    img.put_pixel(2, 2, red);
+    img.put_pixel(4, 4, red);
+
+    let matcher = crate::BoundsCachedMatcher::new(crate::combinator::PixelMatcher(red));
+    let (result, report) = trace(&matcher, &img);
+    assert_eq!(result, Some((2, 2)));
+    assert_eq!(report.candidates.len(), 36);
+    assert_eq!(report.first_match().map(|x| x.bounds), Some((2, 2, 1, 1)));
+    assert_eq!(report.cached_bounds, None);
+    // Tracing leaves the cache of the matcher alone
+    type Image = ImageBuffer<Bgra<u8>, Vec<u8>>;
+    assert_eq!(Matcher::<Image>::cached_bounds(&matcher), None);
+
+    let mut annotated = img.clone();
+    report.annotate(&mut annotated);
+    assert_eq!(annotated.get_pixel(2, 2), &Outcome::Matched.color());
+    assert_eq!(annotated.get_pixel(5, 5), &Outcome::Rejected.color());
+
+    assert_eq!(matcher.find(&img), Some((2, 2)));
+    let (_, report) = trace(&matcher, &img);
+    assert_eq!(report.cached_bounds, Some((2, 2, 1, 1)));
+
+    let mut annotated = img.clone();
+    report.annotate(&mut annotated);
+    assert_eq!(annotated.get_pixel(0, 0), &Bgra([255, 0, 255, 255]));
+}
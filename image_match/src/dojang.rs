@@ -0,0 +1,124 @@
+use assets_embedded::assets;
+use assets_manager::asset::Png;
+use image::{Bgra, GenericImageView, ImageBuffer, SubImage};
+use once_cell::sync::OnceCell;
+
+use crate::{GenericImageViewExt, Matcher};
+
+/// Detects the clear banner shown on the center of the screen when a Mu Lung Dojang floor is
+/// cleared.
+#[derive(Clone)]
+pub struct DojangClearMatcher;
+
+#[derive(Debug, Clone)]
+pub struct DojangClearMatchResult;
+
+static DOJANG_CLEAR: OnceCell<ImageBuffer<Bgra<u8>, Vec<u8>>> = OnceCell::new();
+
+impl<V: GenericImageView<Pixel = Bgra<u8>>> Matcher<V> for DojangClearMatcher {
+    type MatchResult = DojangClearMatchResult;
+
+    type CandidatesIter<'a> = impl Iterator<Item = SubImage<&'a V::InnerImageView>> + 'a where V: 'a;
+
+    fn init() {
+        DOJANG_CLEAR.get_or_init(|| {
+            assets()
+                .load::<Png>("dojang_clear")
+                .unwrap()
+                .cloned()
+                .0
+                .to_bgra8()
+        });
+    }
+
+    fn view_dimensions(&self) -> (u32, u32) {
+        DOJANG_CLEAR.get().unwrap().dimensions()
+    }
+
+    fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a> {
+        // The banner is horizontally centered on the upper half of the screen. Allow a pixel of
+        // error on the center for odd widths.
+        let (w, h) = Matcher::<V>::view_dimensions(self);
+        let center = view.width().saturating_sub(w) / 2;
+        let x = center.saturating_sub(1);
+        let area = (
+            x,
+            0,
+            (w + 2).min(view.width() - x),
+            (view.height() / 2 + h).min(view.height()),
+        );
+        (area.2 >= w && area.3 >= h)
+            .then(|| {
+                view.view(area.0, area.1, area.2, area.3)
+                    .view_bounds_like((w, h), 1)
+                    .map(move |(x, y, w, h)| view.view(x, y, w, h))
+            })
+            .into_iter()
+            .flatten()
+    }
+
+    fn check<'a>(&self, view: &SubImage<&'a V>) -> bool {
+        view.eq(DOJANG_CLEAR.get().unwrap())
+    }
+
+    fn match_image<'a>(&self, _view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        Some(DojangClearMatchResult)
+    }
+}
+
+/// Reports whether the screen is blacked out, as it is while fading between floors.
+#[derive(Clone)]
+pub struct ScreenFadeMatcher;
+
+#[derive(Debug, Clone)]
+pub struct ScreenFadeMatchResult {
+    black: bool,
+}
+
+impl ScreenFadeMatchResult {
+    pub fn black(&self) -> bool {
+        self.black
+    }
+}
+
+/// Distance between the sampled pixels.
+const FADE_SAMPLE_STRIDE: usize = 16;
+const FADE_MAX_VALUE: u8 = 16;
+
+impl<V: GenericImageView<Pixel = Bgra<u8>>> Matcher<V> for ScreenFadeMatcher {
+    type MatchResult = ScreenFadeMatchResult;
+
+    type CandidatesIter<'a> = std::iter::Once<SubImage<&'a V::InnerImageView>> where V: 'a;
+
+    fn view_dimensions(&self) -> (u32, u32) {
+        (0, 0)
+    }
+
+    fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a> {
+        std::iter::once(view.view(0, 0, view.width(), view.height()))
+    }
+
+    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        let black = (0..view.height())
+            .step_by(FADE_SAMPLE_STRIDE)
+            .flat_map(|y| {
+                (0..view.width())
+                    .step_by(FADE_SAMPLE_STRIDE)
+                    .map(move |x| (x, y))
+            })
+            .all(|(x, y)| {
+                let [b, g, r, _] = view.get_pixel(x, y).0;
+                r.max(g).max(b) <= FADE_MAX_VALUE
+            });
+        Some(ScreenFadeMatchResult { black })
+    }
+}
+
+#[test]
+fn screen_fade() {
+    let mut screen = ImageBuffer::from_pixel(320, 240, Bgra([5, 5, 5, 255]));
+    assert!(ScreenFadeMatcher.find(&screen).unwrap().black());
+    // The UI which is left on the screen
+    screen.put_pixel(160, 224, Bgra([200, 200, 200, 255]));
+    assert!(!ScreenFadeMatcher.find(&screen).unwrap().black());
+}
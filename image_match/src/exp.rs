@@ -0,0 +1,162 @@
+use image::{Bgra, GenericImageView, SubImage};
+use once_cell::sync::OnceCell;
+
+use crate::{
+    ocr::{BitmapFont, ColorKey},
+    Matcher,
+};
+
+/// Reads the EXP percentage from the EXP gauge on the bottom of the screen.
+///
+/// The percentage text is read if the glyph sheet `assets/fonts/exp.png` (`0123456789.%[]`) is
+/// available, and the gauge itself is measured otherwise.
+#[derive(Clone)]
+pub struct ExpMatcher;
+
+#[derive(Debug, Clone)]
+pub struct ExpMatchResult {
+    percent: f64,
+    from_text: bool,
+}
+
+impl ExpMatchResult {
+    pub fn percent(&self) -> f64 {
+        self.percent
+    }
+
+    /// Whether the percentage is read from the text, which is more precise than the gauge.
+    pub fn from_text(&self) -> bool {
+        self.from_text
+    }
+}
+
+static EXP_FONT: OnceCell<Option<BitmapFont>> = OnceCell::new();
+
+const EXP_STRIP_HEIGHT: u32 = 12;
+const MIN_CONFIDENCE: f64 = 0.85;
+/// The text over the gauge breaks it into runs; gaps up to this wide are bridged.
+const MAX_GAUGE_GAP: u32 = 16;
+const MIN_GAUGE_WIDTH: u32 = 100;
+
+fn is_gauge_color(p: Bgra<u8>) -> bool {
+    let [b, g, r, _] = p.0;
+    r >= 150 && g >= 150 && b < 100
+}
+
+/// The empty part of the gauge, which is dark gray.
+fn is_track_color(p: Bgra<u8>) -> bool {
+    let [b, g, r, _] = p.0;
+    let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+    (20..90).contains(&max) && max - min < 20
+}
+
+impl ExpMatcher {
+    /// Parses `[12.34%]` in the text, which follows the absolute EXP.
+    fn read_text<I: GenericImageView<Pixel = Bgra<u8>>>(view: &I) -> Option<f64> {
+        let font = EXP_FONT.get()?.as_ref()?;
+        let key = ColorKey::new([Bgra([255, 255, 255, 255])], 40);
+        let text = font.read(view, &key).confident(MIN_CONFIDENCE)?.text();
+        let (_, percent) = text.rsplit_once('[')?;
+        let (percent, _) = percent.split_once('%')?;
+        percent.parse().ok().filter(|x| (0.0..100.0).contains(x))
+    }
+
+    /// Locates the ends of the gauge as the widest span of filled or empty gauge pixels, then
+    /// measures how much of it is filled.
+    fn read_gauge<I: GenericImageView<Pixel = Bgra<u8>>>(view: &I) -> Option<f64> {
+        let y = view.height() / 2;
+        let is_gauge = |x| {
+            let p = view.get_pixel(x, y);
+            is_gauge_color(p) || is_track_color(p)
+        };
+
+        let mut ends = None::<(u32, u32)>;
+        let mut span = None::<(u32, u32)>;
+        for x in (0..view.width()).filter(|&x| is_gauge(x)) {
+            span = match span {
+                Some((left, right)) if x - right <= MAX_GAUGE_GAP => Some((left, x + 1)),
+                _ => Some((x, x + 1)),
+            };
+            let (left, right) = span.unwrap();
+            if !matches!(ends, Some((l, r)) if r - l >= right - left) {
+                ends = Some((left, right));
+            }
+        }
+        let (left, right) = ends.filter(|(l, r)| r - l >= MIN_GAUGE_WIDTH)?;
+
+        // The fill starts from the left end. The text over it may hide its end on some rows.
+        let fill = (0..view.height())
+            .filter_map(|y| {
+                (left..right)
+                    .rev()
+                    .find(|&x| is_gauge_color(view.get_pixel(x, y)))
+            })
+            .max()
+            .map_or(0, |x| x + 1 - left);
+        // An empty gauge is indistinguishable from a missing one
+        (fill > 0).then(|| fill as f64 / (right - left) as f64 * 100.0)
+    }
+}
+
+impl<V: GenericImageView<Pixel = Bgra<u8>>> Matcher<V> for ExpMatcher {
+    type MatchResult = ExpMatchResult;
+
+    type CandidatesIter<'a> = std::iter::Once<SubImage<&'a V::InnerImageView>> where V: 'a;
+
+    fn init() {
+        EXP_FONT.get_or_init(|| BitmapFont::from_asset("fonts.exp", "0123456789.%[]"));
+    }
+
+    fn view_dimensions(&self) -> (u32, u32) {
+        (0, EXP_STRIP_HEIGHT)
+    }
+
+    fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a> {
+        let y = view.height().saturating_sub(EXP_STRIP_HEIGHT);
+        std::iter::once(view.view(0, y, view.width(), view.height() - y))
+    }
+
+    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        match Self::read_text(view) {
+            Some(percent) => Some(ExpMatchResult {
+                percent,
+                from_text: true,
+            }),
+            None => Some(ExpMatchResult {
+                percent: Self::read_gauge(view)?,
+                from_text: false,
+            }),
+        }
+    }
+}
+
+#[test]
+fn read_gauge_between_ends() {
+    use image::ImageBuffer;
+
+    let (left, right) = (40, 440);
+    let mut strip = ImageBuffer::from_pixel(480, EXP_STRIP_HEIGHT, Bgra([0, 0, 0, 255]));
+    for y in 0..EXP_STRIP_HEIGHT {
+        for x in left..right {
+            let color = if x < left + 100 {
+                Bgra([30, 220, 230, 255])
+            } else {
+                Bgra([50, 50, 50, 255])
+            };
+            strip.put_pixel(x, y, color);
+        }
+    }
+    // White text over both the filled and the empty part
+    for x in (left + 80..left + 160).filter(|x| x % 8 < 5) {
+        strip.put_pixel(x, EXP_STRIP_HEIGHT / 2, Bgra([255, 255, 255, 255]));
+    }
+    assert_eq!(ExpMatcher::read_gauge(&strip), Some(25.0));
+
+    // Empty
+    for y in 0..EXP_STRIP_HEIGHT {
+        for x in left..left + 100 {
+            strip.put_pixel(x, y, Bgra([50, 50, 50, 255]));
+        }
+    }
+    assert!(ExpMatcher::read_gauge(&strip).is_none());
+}
@@ -0,0 +1,230 @@
+//! Temporal filters for smoothing out per-frame noise of match results.
+//!
+//! Filters are fed with raw values in the order of screenshots, and return the filtered value.
+//! They can be chained with tuples, e.g. `(OutlierRejection::new(0.1, 3), Median::new(5))`.
+
+use std::collections::VecDeque;
+
+pub trait Filter<T> {
+    /// Feeds a raw value and returns the filtered value.
+    fn push(&mut self, value: T) -> T;
+
+    /// Forgets all previous values.
+    fn reset(&mut self);
+}
+
+impl<T, A: Filter<T>, B: Filter<T>> Filter<T> for (A, B) {
+    fn push(&mut self, value: T) -> T {
+        let value = self.0.push(value);
+        self.1.push(value)
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+        self.1.reset();
+    }
+}
+
+impl<T, A: Filter<T>, B: Filter<T>, C: Filter<T>> Filter<T> for (A, B, C) {
+    fn push(&mut self, value: T) -> T {
+        let value = self.0.push(value);
+        let value = self.1.push(value);
+        self.2.push(value)
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+        self.1.reset();
+        self.2.reset();
+    }
+}
+
+/// Median of the last `window` values.
+#[derive(Debug, Clone)]
+pub struct Median {
+    window: usize,
+    history: VecDeque<f64>,
+}
+
+impl Median {
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0);
+        Self {
+            window,
+            history: VecDeque::with_capacity(window),
+        }
+    }
+}
+
+impl Filter<f64> for Median {
+    fn push(&mut self, value: f64) -> f64 {
+        if self.history.len() == self.window {
+            self.history.pop_front();
+        }
+        self.history.push_back(value);
+
+        let mut sorted = self.history.iter().copied().collect::<Vec<_>>();
+        sorted.sort_by(|x, y| x.partial_cmp(y).unwrap_or(std::cmp::Ordering::Equal));
+        sorted[sorted.len() / 2]
+    }
+
+    fn reset(&mut self) {
+        self.history.clear();
+    }
+}
+
+/// Forces the values to only go in one direction, e.g. boss HP only goes down within a fight.
+#[derive(Debug, Clone)]
+pub struct Monotonic {
+    decreasing: bool,
+    last: Option<f64>,
+}
+
+impl Monotonic {
+    pub fn decreasing() -> Self {
+        Self {
+            decreasing: true,
+            last: None,
+        }
+    }
+
+    pub fn increasing() -> Self {
+        Self {
+            decreasing: false,
+            last: None,
+        }
+    }
+}
+
+impl Filter<f64> for Monotonic {
+    fn push(&mut self, value: f64) -> f64 {
+        let value = match self.last {
+            Some(last) if self.decreasing => last.min(value),
+            Some(last) => last.max(value),
+            None => value,
+        };
+        self.last = Some(value);
+        value
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+/// Ignores values that jump more than `max_deviation` away from the last accepted value.
+///
+/// A jump is accepted if it persists more than `patience` consecutive values, since it is likely a
+/// real change rather than a single misread frame.
+#[derive(Debug, Clone)]
+pub struct OutlierRejection {
+    max_deviation: f64,
+    patience: usize,
+    last: Option<f64>,
+    rejected: usize,
+}
+
+impl OutlierRejection {
+    pub fn new(max_deviation: f64, patience: usize) -> Self {
+        Self {
+            max_deviation,
+            patience,
+            last: None,
+            rejected: 0,
+        }
+    }
+}
+
+impl Filter<f64> for OutlierRejection {
+    fn push(&mut self, value: f64) -> f64 {
+        match self.last {
+            Some(last)
+                if (value - last).abs() > self.max_deviation && self.rejected < self.patience =>
+            {
+                self.rejected += 1;
+                last
+            }
+            _ => {
+                self.rejected = 0;
+                self.last = Some(value);
+                value
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+        self.rejected = 0;
+    }
+}
+
+/// Flips a boolean detection only after it has been stable for a number of consecutive values.
+#[derive(Debug, Clone)]
+pub struct Hysteresis {
+    rise: usize,
+    fall: usize,
+    state: bool,
+    count: usize,
+}
+
+impl Hysteresis {
+    /// `rise` consecutive `true`s are required to turn on, and `fall` consecutive `false`s to turn off.
+    pub fn new(rise: usize, fall: usize) -> Self {
+        Self {
+            rise,
+            fall,
+            state: false,
+            count: 0,
+        }
+    }
+
+    pub fn state(&self) -> bool {
+        self.state
+    }
+}
+
+impl Filter<bool> for Hysteresis {
+    fn push(&mut self, value: bool) -> bool {
+        if value == self.state {
+            self.count = 0;
+            return self.state;
+        }
+
+        self.count += 1;
+        if self.count >= if value { self.rise } else { self.fall } {
+            self.state = value;
+            self.count = 0;
+        }
+        self.state
+    }
+
+    fn reset(&mut self) {
+        self.state = false;
+        self.count = 0;
+    }
+}
+
+#[test]
+fn filters() {
+    let mut median = Median::new(3);
+    let out = [0.5, 0.9, 0.4, 0.4, 0.1].map(|x| median.push(x));
+    assert_eq!(out, [0.5, 0.9, 0.5, 0.4, 0.4]);
+
+    let mut monotonic = Monotonic::decreasing();
+    let out = [0.5, 0.6, 0.4, 0.45].map(|x| monotonic.push(x));
+    assert_eq!(out, [0.5, 0.5, 0.4, 0.4]);
+
+    let mut outlier = OutlierRejection::new(0.1, 2);
+    let out = [0.5, 0.1, 0.48, 0.2, 0.2, 0.2].map(|x| outlier.push(x));
+    assert_eq!(out, [0.5, 0.5, 0.48, 0.48, 0.48, 0.2]);
+
+    let mut hysteresis = Hysteresis::new(2, 3);
+    let out = [
+        true, false, true, true, false, false, true, false, false, false,
+    ]
+    .map(|x| hysteresis.push(x));
+    assert_eq!(
+        out,
+        [false, false, false, true, true, true, true, true, true, false]
+    );
+}
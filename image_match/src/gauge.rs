@@ -0,0 +1,181 @@
+use std::ops::Range;
+
+use assets_embedded::assets;
+use assets_manager::asset::Png;
+use image::{Bgra, GenericImageView, ImageBuffer, SubImage};
+use once_cell::sync::OnceCell;
+
+use crate::{GenericImageViewExt, Matcher};
+
+/// Horizontal span of a gauge, relative to the top left corner of the anchor.
+#[derive(Debug, Clone)]
+pub struct GaugeBar {
+    pub x: Range<u32>,
+    pub y: u32,
+}
+
+impl GaugeBar {
+    /// Ratio of the filled part, measured from the left end of the bar.
+    fn ratio<I>(&self, view: &SubImage<&I>, filled: impl Fn(Bgra<u8>) -> bool) -> f64
+    where
+        I: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        let len = self.x.end - self.x.start;
+        let fill = self
+            .x
+            .clone()
+            .take_while(|&x| filled(view.get_pixel(x, self.y)))
+            .count();
+        fill as f64 / len as f64
+    }
+}
+
+/// Positions of the HP and MP gauges in the status bar.
+#[derive(Debug, Clone)]
+pub struct GaugeLayout {
+    /// `(width, height)` of the status bar area including the anchor.
+    pub dims: (u32, u32),
+    pub hp: GaugeBar,
+    pub mp: GaugeBar,
+}
+
+impl Default for GaugeLayout {
+    fn default() -> Self {
+        Self {
+            dims: (240, 40),
+            hp: GaugeBar { x: 24..224, y: 12 },
+            mp: GaugeBar { x: 24..224, y: 28 },
+        }
+    }
+}
+
+/// Reads the player's HP and MP ratios from the gauges of the status bar.
+///
+/// The status bar is located with the anchor template `assets/player_gauge_anchor.png`. Without it
+/// the matcher never matches; check [`PlayerGaugeMatcher::available`].
+#[derive(Debug, Clone, Default)]
+pub struct PlayerGaugeMatcher {
+    pub layout: GaugeLayout,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerGaugeMatchResult {
+    hp_ratio: f64,
+    mp_ratio: f64,
+}
+
+impl PlayerGaugeMatchResult {
+    pub fn hp_ratio(&self) -> f64 {
+        self.hp_ratio
+    }
+
+    pub fn mp_ratio(&self) -> f64 {
+        self.mp_ratio
+    }
+}
+
+static PLAYER_GAUGE_ANCHOR: OnceCell<Option<ImageBuffer<Bgra<u8>, Vec<u8>>>> = OnceCell::new();
+
+fn is_hp_color(p: Bgra<u8>) -> bool {
+    let [b, g, r, _] = p.0;
+    r >= 150 && r / 2 > g && r / 2 > b
+}
+
+fn is_mp_color(p: Bgra<u8>) -> bool {
+    let [b, _, r, _] = p.0;
+    b >= 150 && b / 2 > r
+}
+
+impl PlayerGaugeMatcher {
+    pub fn available() -> bool {
+        matches!(PLAYER_GAUGE_ANCHOR.get(), Some(Some(_)))
+    }
+}
+
+impl<V: GenericImageView<Pixel = Bgra<u8>>> Matcher<V> for PlayerGaugeMatcher {
+    type MatchResult = PlayerGaugeMatchResult;
+
+    type CandidatesIter<'a> = impl Iterator<Item = SubImage<&'a V::InnerImageView>> + 'a where V: 'a;
+
+    fn init() {
+        PLAYER_GAUGE_ANCHOR.get_or_init(|| {
+            Some(
+                assets()
+                    .load::<Png>("player_gauge_anchor")
+                    .ok()?
+                    .cloned()
+                    .0
+                    .to_bgra8(),
+            )
+        });
+    }
+
+    fn view_dimensions(&self) -> (u32, u32) {
+        self.layout.dims
+    }
+
+    fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a> {
+        // The status bar is on the bottom center of the screen, within the lowest 120 pixels
+        let (w, h) = Matcher::<V>::view_dimensions(self);
+        let (x, y) = (view.width() / 4, view.height().saturating_sub(120));
+        let area = (
+            x,
+            y,
+            (view.width() / 2 + w).min(view.width() - x),
+            view.height() - y,
+        );
+        let fits = area.2 >= w && area.3 >= h;
+        (Self::available() && fits)
+            .then(|| {
+                view.view(area.0, area.1, area.2, area.3)
+                    .view_bounds_like((w, h), 1)
+                    .map(move |(x, y, w, h)| view.view(x, y, w, h))
+            })
+            .into_iter()
+            .flatten()
+    }
+
+    fn check<'a>(&self, view: &SubImage<&'a V>) -> bool {
+        let anchor = match PLAYER_GAUGE_ANCHOR.get() {
+            Some(Some(anchor)) => anchor,
+            _ => return false,
+        };
+        anchor.width() <= view.width()
+            && anchor.height() <= view.height()
+            && view.view(0, 0, anchor.width(), anchor.height()).eq(anchor)
+    }
+
+    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        Some(PlayerGaugeMatchResult {
+            hp_ratio: self.layout.hp.ratio(view, is_hp_color),
+            mp_ratio: self.layout.mp.ratio(view, is_mp_color),
+        })
+    }
+}
+
+#[test]
+fn player_gauge_on_small_capture() {
+    let anchor = ImageBuffer::from_fn(8, 8, |x, y| Bgra([x as u8 * 30, y as u8 * 30, 100, 255]));
+    PLAYER_GAUGE_ANCHOR.get_or_init(|| Some(anchor.clone()));
+    let matcher = PlayerGaugeMatcher::default();
+
+    // A capture lower than the area searched for the status bar
+    let black = Bgra([0, 0, 0, 255]);
+    let mut screen = ImageBuffer::from_pixel(800, 60, black);
+    let (x0, y0) = (250, 10);
+    image::imageops::replace(&mut screen, &anchor, x0, y0);
+    for x in 0..150 {
+        screen.put_pixel(x0 + 24 + x, y0 + 12, Bgra([30, 30, 220, 255]));
+    }
+    for x in 0..50 {
+        screen.put_pixel(x0 + 24 + x, y0 + 28, Bgra([220, 60, 30, 255]));
+    }
+    let result = matcher.find(&screen).unwrap();
+    assert!((result.hp_ratio() - 0.75).abs() < 0.01);
+    assert!((result.mp_ratio() - 0.25).abs() < 0.01);
+
+    // Too small for the status bar
+    assert!(matcher
+        .find(&ImageBuffer::from_pixel(200, 30, black))
+        .is_none());
+}
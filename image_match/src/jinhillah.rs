@@ -1,4 +1,4 @@
-use std::ops::Range;
+use std::sync::Arc;
 
 use assets_embedded::assets;
 use assets_manager::asset::Png;
@@ -6,132 +6,151 @@ use image::{Bgra, GenericImageView, ImageBuffer, SubImage};
 use once_cell::sync::OnceCell;
 use rayon::prelude::*;
 
-use crate::{view_ext::GenericImageViewExt, Matcher, RegularizedEqPixel};
-
-pub struct JinHillahHpMatcher;
+use crate::{Matcher, RegularizedEqPixel};
 
+/// Which frames of `assets/jinhillah_reap/` the reap matcher uses, and how they are matched.
 #[derive(Debug, Clone)]
-pub struct JinHillahHpMatchResult {
-    level: usize,
-    remaining_pixels: u32,
+pub struct ReapTemplateConfig {
+    /// Frame numbers to use. `None` uses the whole animation.
+    pub frames: Option<Vec<usize>>,
+    /// The center `1 / scale` of each frame is magnified by `scale` into the template.
+    pub scale: u32,
+    /// A frame matches if less than this ratio of its good pixels differ from the screen.
+    pub max_mismatch: f64,
 }
 
-impl JinHillahHpMatchResult {
-    pub fn phase(&self) -> u32 {
-        if self.level == 0 {
-            1
-        } else if self.level <= 4 {
-            self.level as u32
-        } else {
-            unreachable!();
+impl Default for ReapTemplateConfig {
+    fn default() -> Self {
+        Self {
+            frames: None,
+            scale: 2,
+            max_mismatch: 0.5,
         }
     }
+}
 
-    pub fn hp_ratio(&self) -> f64 {
-        self.remaining_pixels as f64 / MAX_PIXELS as f64
+impl ReapTemplateConfig {
+    /// Magnifies the center `1 / scale` of a frame into a template of the same size.
+    pub fn magnify<I: GenericImageView<Pixel = Bgra<u8>>>(
+        &self,
+        img: &I,
+    ) -> ImageBuffer<Bgra<u8>, Vec<u8>> {
+        let scale = self.scale;
+        let (ox, oy) = (
+            (img.width() - img.width() / scale) / 2,
+            (img.height() - img.height() / scale) / 2,
+        );
+        ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+            img.get_pixel(x / scale + ox, y / scale + oy)
+        })
     }
-}
 
-static JIN_HILLAH_HP_ICON: OnceCell<ImageBuffer<Bgra<u8>, Vec<u8>>> = OnceCell::new();
-
-const HP_BAR_COLORS: [(Bgra<u8>, Bgra<u8>); 4] = [
-    (Bgra([102, 68, 204, 255]), Bgra([102, 68, 187, 255])),
-    (Bgra([153, 102, 238, 255]), Bgra([153, 102, 221, 255])),
-    (Bgra([34, 170, 170, 255]), Bgra([17, 153, 136, 255])),
-    (Bgra([17, 119, 85, 255]), Bgra([17, 102, 68, 255])),
-];
-
-fn find_color(pair: (Bgra<u8>, Bgra<u8>)) -> usize {
-    HP_BAR_COLORS
-        .iter()
-        .enumerate()
-        .find(|(_, x)| x == &&pair)
-        .map(|x| x.0)
-        .unwrap_or(4)
+    /// Ratio of the good pixels of a magnified frame which differ from the view, as compared
+    /// against [`Self::max_mismatch`].
+    pub fn mismatch_ratio<I: GenericImageView<Pixel = Bgra<u8>>>(
+        template: &ImageBuffer<Bgra<u8>, Vec<u8>>,
+        view: &I,
+    ) -> f64 {
+        let (good, mismatches) = template
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p.good_pixel())
+            .fold((0usize, 0usize), |(good, mismatches), (x, y, p)| {
+                (good + 1, mismatches + (view.get_pixel(x, y) != *p) as usize)
+            });
+        mismatches as f64 / good.max(1) as f64
+    }
 }
 
-const HP_X_OFFSET: Range<u32> = 40..796; // Note: Y is 9/10 and 8/9 for x = 1035
-const MAX_PIXELS: u32 = HP_X_OFFSET.end - HP_X_OFFSET.start + 1;
-
-impl<V: GenericImageView<Pixel = Bgra<u8>>> Matcher<V> for JinHillahHpMatcher {
-    type MatchResult = JinHillahHpMatchResult;
-    type CandidatesIter<'a> = impl Iterator<Item = SubImage<&'a V::InnerImageView>> + 'a where V: 'a;
-
-    fn init() {
-        let asset = assets();
-        let img = asset
-            .load::<Png>("jinhillah_boss_hpbar_icon")
-            .unwrap()
-            .cloned()
-            .0
-            .to_bgra8();
-
-        JIN_HILLAH_HP_ICON.set(img).unwrap();
-    }
+#[derive(Clone)]
+struct ReapTemplate {
+    frame: usize,
+    image: ImageBuffer<Bgra<u8>, Vec<u8>>,
+    good_pixels: usize,
+}
 
-    fn view_dimensions(&self) -> (u32, u32) {
-        (800, 37)
-    }
+/// Matches the reap animation of Jin Hillah on the center of the screen. Results are the frame
+/// numbers of the animation.
+#[derive(Clone)]
+pub struct JinHillahReapMatcher {
+    dims: (u32, u32),
+    /// Shared by clones, as the templates are large.
+    templates: Arc<[ReapTemplate]>,
+    max_mismatch: f64,
+}
 
-    fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a> {
-        let icon = JIN_HILLAH_HP_ICON.get().unwrap();
-        view.view(0, 0, view.width(), icon.height())
-            .view_bounds_like((Matcher::<V>::view_dimensions(self).0, icon.height()), 1)
-            .map(move |(x, y, w, h)| view.view(x, y, w, h))
-    }
+/// `(frame number, image)` of the animation, sorted by the frame number.
+#[allow(clippy::type_complexity)]
+static JIN_HILLAH_REAP_MOTIONS: OnceCell<Vec<(usize, ImageBuffer<Bgra<u8>, Vec<u8>>)>> =
+    OnceCell::new();
 
-    fn check<'a>(&self, view: &SubImage<&'a V>) -> bool {
-        let icon = JIN_HILLAH_HP_ICON.get().unwrap();
-        view.view(3, 3, icon.width(), icon.height()).eq(icon)
+impl JinHillahReapMatcher {
+    /// `dims` is the dimension of the screen.
+    pub fn new(dims: (u32, u32)) -> Self {
+        Self::with_config(dims, &ReapTemplateConfig::default())
     }
 
-    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
-        let mut last_idx = 0;
-        let mut changed_x = None;
-        let it = HP_X_OFFSET
-            .map(|x| {
-                let p1 = view.get_pixel(x, 9);
-                let p2 = view.get_pixel(x, 10);
-                (p1, p2)
+    pub fn with_config(dims: (u32, u32), config: &ReapTemplateConfig) -> Self {
+        assert!(config.scale > 0);
+        let templates = JIN_HILLAH_REAP_MOTIONS
+            .get()
+            .expect("JinHillahReapMatcher is not initialized")
+            .iter()
+            .filter(|(frame, _)| match &config.frames {
+                Some(frames) => frames.contains(frame),
+                None => true,
             })
-            .chain(std::iter::once((
-                view.get_pixel(HP_X_OFFSET.end, 8),
-                view.get_pixel(HP_X_OFFSET.end, 9),
-            )))
-            .enumerate()
-            .map(|(i, pair)| (i, find_color(pair)));
-
-        for (i, idx) in it {
-            if i == 0 {
-                last_idx = idx;
-                continue;
-            }
+            .map(|(frame, img)| {
+                let image = config.magnify(img);
+                let good_pixels = image.pixels().filter(|x| x.good_pixel()).count();
+                ReapTemplate {
+                    frame: *frame,
+                    image,
+                    good_pixels,
+                }
+            })
+            .collect::<Vec<_>>();
+        assert!(!templates.is_empty(), "no reap frames are selected");
 
-            if idx == last_idx {
-                continue;
-            }
+        Self {
+            dims,
+            templates: templates.into(),
+            max_mismatch: config.max_mismatch,
+        }
+    }
 
-            if changed_x.is_some() {
-                return None;
+    fn template_matches<I: GenericImageView<Pixel = Bgra<u8>>>(
+        &self,
+        template: &ReapTemplate,
+        view: &I,
+    ) -> bool {
+        let max_mismatches = (template.good_pixels as f64 * self.max_mismatch) as usize;
+        let mut mismatches = 0;
+        for (x, y, p) in view.pixels() {
+            let q = template.image.get_pixel(x, y);
+            if q.good_pixel() && q != &p {
+                mismatches += 1;
+                if mismatches >= max_mismatches {
+                    break;
+                }
             }
-
-            changed_x = Some(i as u32);
-            last_idx = idx;
         }
+        mismatches < max_mismatches
+    }
 
-        Some(JinHillahHpMatchResult {
-            level: last_idx,
-            remaining_pixels: changed_x.unwrap_or(MAX_PIXELS),
-        })
+    /// Evaluation mode of [`Matcher::match_image`]. Returns every frame matching the view instead
+    /// of the first one, to find out which frames contribute to detections.
+    pub fn matching_frames<I>(&self, view: &I) -> Vec<usize>
+    where
+        I: GenericImageView<Pixel = Bgra<u8>> + Sync,
+    {
+        self.templates
+            .par_iter()
+            .filter(|template| self.template_matches(template, view))
+            .map(|template| template.frame)
+            .collect()
     }
 }
 
-pub struct JinHillahReapMatcher(pub u32, pub u32);
-
-#[allow(clippy::type_complexity)]
-static JIN_HILLAH_REAP_MOTIONS: OnceCell<Vec<(ImageBuffer<Bgra<u8>, Vec<u8>>, usize)>> =
-    OnceCell::new();
-
 impl<V: GenericImageView<Pixel = Bgra<u8>> + std::marker::Sync> Matcher<V>
     for JinHillahReapMatcher
 {
@@ -141,39 +160,30 @@ impl<V: GenericImageView<Pixel = Bgra<u8>> + std::marker::Sync> Matcher<V>
 
     fn init() {
         JIN_HILLAH_REAP_MOTIONS.get_or_init(|| {
-            let imgs = assets()
+            let mut frames = assets()
                 .load_dir::<Png>("jinhillah_reap", false)
                 .unwrap()
                 .iter()
-                .take(12)
-                .map(Result::unwrap);
-            imgs.map(|asset| {
-                let img = asset.cloned().0.to_bgra8();
-                let img = ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
-                    *img.get_pixel(x / 2 + img.width() / 4, y / 2 + img.height() / 4)
-                });
-                let cnt = img.pixels().filter(|x| x.good_pixel()).count();
-                (img, cnt)
-            })
-            .collect::<Vec<_>>()
+                .map(Result::unwrap)
+                .map(|asset| {
+                    let (_, name) = asset.id().rsplit_once('.').unwrap();
+                    (name.parse().unwrap(), asset.cloned().0.to_bgra8())
+                })
+                .collect::<Vec<_>>();
+            frames.sort_by_key(|(frame, _)| *frame);
+            frames
         });
     }
 
     fn view_dimensions(&self) -> (u32, u32) {
-        (self.0 / 2, self.1 / 2)
+        (self.dims.0 / 2, self.dims.1 / 2)
     }
 
     fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a> {
-        if (self.0, self.1) == view.dimensions() {
+        if self.dims == view.dimensions() {
             const RANGE: i32 = 2;
             let offsets = (-RANGE..=RANGE).flat_map(|x| (-RANGE..=RANGE).map(move |y| (x, y)));
-            let dims = JIN_HILLAH_REAP_MOTIONS
-                .get()
-                .unwrap()
-                .first()
-                .unwrap()
-                .0
-                .dimensions();
+            let dims = self.templates.first().unwrap().image.dimensions();
             let center = ((view.width() - dims.0) / 2, (view.height() - dims.1) / 2);
             Some(
                 offsets
@@ -189,27 +199,55 @@ impl<V: GenericImageView<Pixel = Bgra<u8>> + std::marker::Sync> Matcher<V>
         }
     }
 
+    /// The first matching frame, as the templates are sorted by the frame number.
     fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
-        JIN_HILLAH_REAP_MOTIONS
-            .get()
-            .unwrap()
-            .par_iter()
-            .enumerate()
-            .find_map_any(|(i, (img, good_pixels))| {
-                let mut bad_count_remaining = (*good_pixels as f64 * 0.5) as usize;
-                for (x, y, p) in view.pixels() {
-                    let q = img.get_pixel(x, y);
-                    if q.good_pixel() && q != &p {
-                        bad_count_remaining -= 1;
-                        if bad_count_remaining == 0 {
-                            break;
-                        }
-                    }
-                }
-                if bad_count_remaining > 0 {
-                    return Some(i);
-                }
+        self.templates.par_iter().find_map_first(|template| {
+            if self.template_matches(template, view) {
+                Some(template.frame)
+            } else {
                 None
+            }
+        })
+    }
+}
+
+#[test]
+fn reap_first_matching_frame() {
+    let image = ImageBuffer::from_fn(16, 16, |x, y| Bgra([x as u8 * 16, y as u8 * 16, 128, 255]));
+    let good_pixels = image.pixels().filter(|x| x.good_pixel()).count();
+    let matcher = JinHillahReapMatcher {
+        dims: (32, 32),
+        // Consecutive frames of the animation are often alike
+        templates: (0..64)
+            .map(|frame| ReapTemplate {
+                frame,
+                image: image.clone(),
+                good_pixels,
             })
+            .collect(),
+        max_mismatch: 0.1,
+    };
+    let screen = image.clone();
+    for _ in 0..10 {
+        assert_eq!(matcher.match_image(&screen.view(0, 0, 16, 16)), Some(0));
     }
 }
+
+#[test]
+fn reap_magnified_mismatch() {
+    let frame = ImageBuffer::from_fn(8, 8, |x, y| {
+        Bgra([x as u8 * 16 + 1, y as u8 * 16 + 1, 128, 255])
+    });
+    let config = ReapTemplateConfig::default();
+    let template = config.magnify(&frame);
+    // The center 4x4 of the frame fills the template
+    assert_eq!(template.get_pixel(0, 0), frame.get_pixel(2, 2));
+    assert_eq!(template.get_pixel(7, 7), frame.get_pixel(5, 5));
+
+    assert_eq!(
+        ReapTemplateConfig::mismatch_ratio(&template, &template),
+        0.0
+    );
+    // The frame itself differs from the magnified template almost everywhere
+    assert!(ReapTemplateConfig::mismatch_ratio(&template, &frame) > 0.5);
+}
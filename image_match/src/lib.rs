@@ -1,11 +1,35 @@
 #![feature(type_alias_impl_trait, generic_associated_types)]
 
+pub mod boss_hp;
 pub mod buff;
+pub mod color;
+mod combinator;
+pub mod debuff;
+pub mod debug;
+pub mod dojang;
+pub mod exp;
+pub mod filter;
+pub mod gauge;
 pub mod jinhillah;
+pub mod message;
+pub mod minimap;
+pub mod ocr;
+pub mod phash;
+pub mod quickslot;
+pub mod template;
 mod view_ext;
 
-use std::{cell::Cell, fmt::Debug};
+use std::{
+    cell::Cell,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
+pub use combinator::{AllOf, AnyOf, Gate, Map};
 use image::{Bgra, GenericImageView, SubImage};
 pub use view_ext::*;
 
@@ -15,7 +39,8 @@ pub trait Matcher<V: GenericImageView<Pixel = Bgra<u8>>> {
     where
         V: 'a;
 
-    // Runned once at program initialization. Intended for Once(Cell)-like static resources.
+    // Runned at program initialization. Intended for Once(Cell)-like static resources.
+    // May be called more than once when the matcher is wrapped by combinators.
     fn init() {}
 
     /// `(width, height)` pair of views that the matcher wants.
@@ -37,13 +62,127 @@ pub trait Matcher<V: GenericImageView<Pixel = Bgra<u8>>> {
 
     /// Main match routine.
     fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult>;
+
+    /// Bounds tried first on the next screen, for matchers which cache them. Used for debugging.
+    fn cached_bounds(&self) -> Option<(u32, u32, u32, u32)> {
+        None
+    }
+
+    /// Runs [`Matcher::check`] and [`Matcher::match_image`] over all candidates of the screen,
+    /// and returns the first match result.
+    fn find(&self, view: &V) -> Option<Self::MatchResult>
+    where
+        V: GenericImageView<InnerImageView = V>,
+    {
+        self.candidates_iter(view)
+            .filter(|candidate| self.check(candidate))
+            .find_map(|candidate| self.match_image(&candidate))
+    }
+}
+
+/// When to throw away the bounds cached by [`BoundsCachedMatcher`] and scan the whole screen again.
+///
+/// The default policy never invalidates the cache.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InvalidationPolicy {
+    /// Clear the cache after this many consecutive screens without a match on the cached bounds.
+    pub max_misses: Option<u32>,
+    /// Clear the cache when it gets older than this.
+    pub max_age: Option<Duration>,
+    /// Scan the whole screen with this interval even if the cache is in use, and move the cache to
+    /// the new bounds if found. The scan is spread over screens, [`RESCAN_BATCH`] candidates at a
+    /// time, so it does not delay a match on the cached bounds.
+    pub rescan_interval: Option<Duration>,
+}
+
+/// Number of candidates checked on each screen by the rescan of [`InvalidationPolicy`].
+pub const RESCAN_BATCH: usize = 256;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct CacheState {
+    bounds: Option<(u32, u32, u32, u32)>,
+    cached_at: Option<Instant>,
+    /// When the last full scan finished.
+    last_rescan: Option<Instant>,
+    /// Index of the next candidate of the rescan in progress.
+    rescan_from: Option<usize>,
+    misses: u32,
+    /// Whether the cached bounds were supplied on the last screen.
+    used: bool,
+    hit: bool,
+}
+
+/// Handle to clear the cache of a [`BoundsCachedMatcher`] from other threads.
+#[derive(Debug, Clone)]
+pub struct BoundsInvalidator(Arc<AtomicBool>);
+
+impl BoundsInvalidator {
+    pub fn invalidate(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
 }
 
-pub struct BoundsCachedMatcher<T>(T, Cell<Option<(u32, u32, u32, u32)>>);
+pub struct BoundsCachedMatcher<T> {
+    inner: T,
+    policy: InvalidationPolicy,
+    state: Cell<CacheState>,
+    invalidated: Arc<AtomicBool>,
+}
+
+/// Clones cache on their own, and are not cleared by the [`BoundsInvalidator`] of the original.
+impl<T: Clone> Clone for BoundsCachedMatcher<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            policy: self.policy,
+            state: self.state.clone(),
+            invalidated: Arc::new(AtomicBool::new(self.invalidated.load(Ordering::SeqCst))),
+        }
+    }
+}
 
 impl<T> BoundsCachedMatcher<T> {
     pub fn new(x: T) -> Self {
-        Self(x, Cell::new(None))
+        Self::with_policy(x, InvalidationPolicy::default())
+    }
+
+    pub fn with_policy(x: T, policy: InvalidationPolicy) -> Self {
+        Self {
+            inner: x,
+            policy,
+            state: Cell::new(CacheState::default()),
+            invalidated: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Returns a handle that clears the cache on the next screen.
+    pub fn invalidator(&self) -> BoundsInvalidator {
+        BoundsInvalidator(Arc::clone(&self.invalidated))
+    }
+
+    /// Applies the invalidation policy. Called once per screen.
+    fn refresh(&self, now: Instant) -> CacheState {
+        let mut state = self.state.get();
+        if state.used {
+            state.misses = if state.hit { 0 } else { state.misses + 1 };
+        }
+        state.used = false;
+        state.hit = false;
+
+        let invalidated = self.invalidated.swap(false, Ordering::SeqCst);
+        let too_many_misses = matches!(self.policy.max_misses, Some(max) if state.misses >= max);
+        let too_old = matches!(
+            (self.policy.max_age, state.cached_at),
+            (Some(max_age), Some(cached_at)) if now.saturating_duration_since(cached_at) >= max_age
+        );
+        if invalidated || too_many_misses || too_old {
+            state.bounds = None;
+            state.cached_at = None;
+            state.misses = 0;
+            state.rescan_from = None;
+        }
+
+        state
     }
 }
 
@@ -54,27 +193,50 @@ impl<
 {
     type MatchResult = T::MatchResult;
 
-    type CandidatesIter<'a> = std::vec::IntoIter<SubImage<&'a V::InnerImageView>>
+    type CandidatesIter<'a> = impl Iterator<Item = SubImage<&'a V::InnerImageView>>
         where <V as GenericImageView>::InnerImageView: 'a, V: 'a;
 
+    fn init() {
+        T::init();
+    }
+
     fn view_dimensions(&self) -> (u32, u32) {
-        self.0.view_dimensions()
+        self.inner.view_dimensions()
     }
 
     fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a>
     where
         V: GenericImageView<Pixel = Bgra<u8>>,
     {
-        if let Some(bounds) = self.1.get() {
-            vec![view.inner().view(bounds.0, bounds.1, bounds.2, bounds.3)].into_iter()
+        let mut state = self.refresh(Instant::now());
+        let cached = state.bounds.map(|bounds| {
+            state.used = true;
+            view.inner().view(bounds.0, bounds.1, bounds.2, bounds.3)
+        });
+        let scan = if cached.is_none() {
+            state.last_rescan = Some(Instant::now());
+            Some(self.inner.candidates_iter(view))
         } else {
-            self.0.candidates_iter(view).collect::<Vec<_>>().into_iter()
-        }
+            None
+        };
+        self.state.set(state);
+
+        cached.into_iter().chain(scan.into_iter().flatten())
+    }
+
+    fn cached_bounds(&self) -> Option<(u32, u32, u32, u32)> {
+        self.state.get().bounds
     }
 
     fn check<'a>(&self, view: &SubImage<&'a V>) -> bool {
-        if self.0.check(view) {
-            self.1.set(Some(view.bounds()));
+        if self.inner.check(view) {
+            let mut state = self.state.get();
+            if state.bounds != Some(view.bounds()) {
+                state.bounds = Some(view.bounds());
+                state.cached_at = Some(Instant::now());
+                state.misses = 0;
+            }
+            self.state.set(state);
             true
         } else {
             false
@@ -82,7 +244,74 @@ impl<
     }
 
     fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
-        self.0.match_image(view)
+        let result = self.inner.match_image(view);
+        if result.is_some() {
+            let mut state = self.state.get();
+            state.hit = true;
+            self.state.set(state);
+        }
+        result
+    }
+
+    /// Matches the cached bounds, and continues the rescan of [`InvalidationPolicy`] whether they
+    /// match or not.
+    fn find(&self, view: &V) -> Option<Self::MatchResult> {
+        let cached = self.state.get().bounds.is_some();
+        let result = self
+            .candidates_iter(view)
+            .filter(|candidate| self.check(candidate))
+            .find_map(|candidate| self.match_image(&candidate));
+        if !cached {
+            return result;
+        }
+
+        let now = Instant::now();
+        let mut state = self.state.get();
+        let bounds = match state.bounds {
+            Some(bounds) => bounds,
+            None => return result,
+        };
+        let from = match (
+            state.rescan_from,
+            self.policy.rescan_interval,
+            state.last_rescan,
+        ) {
+            (Some(from), _, _) => from,
+            (None, Some(interval), Some(last))
+                if now.saturating_duration_since(last) >= interval =>
+            {
+                0
+            }
+            _ => return result,
+        };
+
+        let mut scanned = 0;
+        let found = self
+            .inner
+            .candidates_iter(view)
+            .skip(from)
+            .take(RESCAN_BATCH)
+            .inspect(|_| scanned += 1)
+            .filter(|candidate| candidate.bounds() != bounds && self.inner.check(candidate))
+            .find_map(|candidate| Some((candidate.bounds(), self.inner.match_image(&candidate)?)));
+        if let Some((new_bounds, new_result)) = found {
+            state.bounds = Some(new_bounds);
+            state.cached_at = Some(now);
+            state.misses = 0;
+            state.hit = true;
+            state.rescan_from = None;
+            state.last_rescan = Some(now);
+            self.state.set(state);
+            return result.or(Some(new_result));
+        }
+        if scanned < RESCAN_BATCH {
+            state.rescan_from = None;
+            state.last_rescan = Some(now);
+        } else {
+            state.rescan_from = Some(from + scanned);
+        }
+        self.state.set(state);
+        result
     }
 }
 
@@ -97,3 +326,67 @@ fn check_sanity() {
     let view2 = view.inner().view(bounds.0, bounds.1, bounds.2, bounds.3);
     assert!(view.eq(&view2));
 }
+
+#[test]
+fn bounds_cache_invalidation() {
+    let red = Bgra([0, 0, 255, 255]);
+    let mut img = image::ImageBuffer::from_pixel(4, 4, Bgra([0, 0, 0, 255]));
+    img.put_pixel(1, 2, red);
+
+    let matcher = BoundsCachedMatcher::with_policy(
+        combinator::PixelMatcher(red),
+        InvalidationPolicy {
+            max_misses: Some(2),
+            ..Default::default()
+        },
+    );
+    assert_eq!(matcher.find(&img), Some((1, 2)));
+    assert_eq!(
+        Matcher::<image::ImageBuffer<Bgra<u8>, Vec<u8>>>::cached_bounds(&matcher),
+        Some((1, 2, 1, 1))
+    );
+
+    img.put_pixel(1, 2, Bgra([0, 0, 0, 255]));
+    img.put_pixel(3, 3, red);
+    assert_eq!(matcher.find(&img), None);
+    assert_eq!(matcher.find(&img), None);
+    assert_eq!(matcher.find(&img), Some((3, 3)));
+
+    matcher.invalidator().invalidate();
+    assert_eq!(matcher.candidates_iter(&img).count(), 16);
+}
+
+#[test]
+fn bounds_cache_rescan_in_batches() {
+    let cached_bounds = |matcher: &BoundsCachedMatcher<combinator::PixelMatcher>| {
+        Matcher::<image::ImageBuffer<Bgra<u8>, Vec<u8>>>::cached_bounds(matcher)
+    };
+    let red = Bgra([0, 0, 255, 255]);
+    let mut img = image::ImageBuffer::from_pixel(32, 32, Bgra([0, 0, 0, 255]));
+    img.put_pixel(1, 2, red);
+
+    let matcher = BoundsCachedMatcher::with_policy(
+        combinator::PixelMatcher(red),
+        InvalidationPolicy {
+            rescan_interval: Some(Duration::ZERO),
+            ..Default::default()
+        },
+    );
+    assert_eq!(matcher.find(&img), Some((1, 2)));
+    assert_eq!(matcher.candidates_iter(&img).count(), 1);
+
+    // The rescan goes on while the cached bounds still match
+    img.put_pixel(31, 31, red);
+    for _ in 0..32 * 32 / RESCAN_BATCH - 1 {
+        assert_eq!(matcher.find(&img), Some((1, 2)));
+        assert_eq!(cached_bounds(&matcher), Some((1, 2, 1, 1)));
+    }
+    assert_eq!(matcher.find(&img), Some((1, 2)));
+    assert_eq!(cached_bounds(&matcher), Some((31, 31, 1, 1)));
+
+    // and finds the new bounds when they stop matching
+    img.put_pixel(31, 31, Bgra([0, 0, 0, 255]));
+    img.put_pixel(5, 0, red);
+    assert_eq!(matcher.find(&img), Some((5, 0)));
+    assert_eq!(cached_bounds(&matcher), Some((5, 0, 1, 1)));
+}
@@ -0,0 +1,295 @@
+//! Recognizing system messages in the chat area.
+//!
+//! Pixels of a message color are grouped into text lines, and each line is reduced to the bit
+//! patterns of its columns. A line is recognized if it starts with the columns of a known
+//! [`MessageTemplate`], so templates only need the fixed part of a message. Of the templates
+//! sharing a prefix, the one equal to the whole line or else the longest one is taken.
+
+use std::fmt::Debug;
+
+use image::{Bgra, GenericImageView, SubImage};
+
+use crate::{ocr::ColorKey, Matcher};
+
+/// Column bit patterns of a text line, with the bit `n` set for a text pixel on the row `n`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineShape(Vec<u32>);
+
+impl LineShape {
+    /// FNV-1a hash of the shape. Equal texts in the same color and font have equal hashes.
+    pub fn hash(&self) -> u64 {
+        self.0
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .fold(0xcbf29ce484222325, |h, b| {
+                (h ^ b as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+
+    /// Whether the shape starts with the other. The rows of the two may be off by a few pixels,
+    /// since a line is as tall as its tallest glyph.
+    fn starts_with(&self, prefix: &LineShape) -> bool {
+        const MAX_SHIFT: i32 = 4;
+        prefix.0.len() <= self.0.len()
+            && (-MAX_SHIFT..=MAX_SHIFT).any(|shift| {
+                prefix.0.iter().zip(&self.0).all(|(&p, &x)| {
+                    if shift >= 0 {
+                        p << shift == x
+                    } else {
+                        p >> -shift == x
+                    }
+                })
+            })
+    }
+}
+
+/// Rows of the view with any text pixel, grouped into lines as `(top, bottom)` pairs.
+fn line_ranges<I>(view: &I, key: &ColorKey) -> Vec<(u32, u32)>
+where
+    I: GenericImageView<Pixel = Bgra<u8>>,
+{
+    /// Lines are at most this tall, and rows further apart belong to different lines.
+    const MAX_LINE_HEIGHT: u32 = 32;
+    const MAX_ROW_GAP: u32 = 1;
+
+    let rows = (0..view.height())
+        .filter(|&y| (0..view.width()).any(|x| key.contains(view.get_pixel(x, y))))
+        .collect::<Vec<_>>();
+
+    let mut lines: Vec<(u32, u32)> = Vec::new();
+    for y in rows {
+        match lines.last_mut() {
+            Some((top, bottom)) if y <= *bottom + MAX_ROW_GAP && y - *top < MAX_LINE_HEIGHT => {
+                *bottom = y
+            }
+            _ => lines.push((y, y)),
+        }
+    }
+    lines
+}
+
+fn line_shape<I>(view: &I, key: &ColorKey, (top, bottom): (u32, u32)) -> LineShape
+where
+    I: GenericImageView<Pixel = Bgra<u8>>,
+{
+    let columns = (0..view.width())
+        .map(|x| {
+            (top..=bottom)
+                .filter(|&y| key.contains(view.get_pixel(x, y)))
+                .fold(0u32, |bits, y| bits | 1 << (y - top))
+        })
+        .collect::<Vec<_>>();
+    let start = columns.iter().position(|&x| x != 0).unwrap_or(0);
+    let end = columns
+        .iter()
+        .rposition(|&x| x != 0)
+        .map_or(start, |x| x + 1);
+    LineShape(columns[start..end].to_vec())
+}
+
+/// A known message, identified by `kind`.
+#[derive(Debug, Clone)]
+pub struct MessageTemplate<K> {
+    pub kind: K,
+    pub key: ColorKey,
+    shape: LineShape,
+}
+
+impl<K> MessageTemplate<K> {
+    /// Takes the first line of the color in the image, which is usually a crop of a screenshot.
+    pub fn from_image<I>(kind: K, image: &I, key: ColorKey) -> Option<Self>
+    where
+        I: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        let range = *line_ranges(image, &key).first()?;
+        let shape = line_shape(image, &key, range);
+        Some(Self { kind, key, shape })
+    }
+}
+
+/// A text line found in the chat area.
+#[derive(Debug, Clone)]
+pub struct MessageLine<K> {
+    /// Kind of the matching template, if any.
+    pub kind: Option<K>,
+    /// Top of the line in the view.
+    pub y: u32,
+    pub shape: LineShape,
+}
+
+#[derive(Debug, Clone)]
+pub struct MessageMatchResult<K> {
+    /// Lines from the top.
+    pub lines: Vec<MessageLine<K>>,
+}
+
+/// Finds text lines in the colors of the templates on the chat area, and recognizes them.
+#[derive(Clone)]
+pub struct MessageMatcher<K> {
+    templates: Vec<MessageTemplate<K>>,
+    /// `(x, y, width, height)` of the chat area, as ratios of the screen size.
+    region: (f64, f64, f64, f64),
+}
+
+/// Chat area of the default UI layout.
+pub const CHAT_REGION: (f64, f64, f64, f64) = (0.0, 0.6, 0.45, 0.3);
+
+impl<K: Clone> MessageMatcher<K> {
+    pub fn new(templates: Vec<MessageTemplate<K>>, region: (f64, f64, f64, f64)) -> Self {
+        Self { templates, region }
+    }
+
+    /// Distinct color keys of the templates.
+    fn keys(&self) -> Vec<&ColorKey> {
+        let mut keys: Vec<&ColorKey> = Vec::new();
+        for template in &self.templates {
+            if !keys.contains(&&template.key) {
+                keys.push(&template.key);
+            }
+        }
+        keys
+    }
+
+    pub fn read<I>(&self, view: &I) -> Vec<MessageLine<K>>
+    where
+        I: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        let mut lines = self
+            .keys()
+            .into_iter()
+            .flat_map(|key| {
+                line_ranges(view, key)
+                    .into_iter()
+                    .map(move |range| (key, range))
+            })
+            .map(|(key, range)| {
+                let shape = line_shape(view, key, range);
+                let kind = self
+                    .templates
+                    .iter()
+                    .filter(|t| &t.key == key && shape.starts_with(&t.shape))
+                    .max_by_key(|t| (t.shape.hash() == shape.hash(), t.shape.0.len()))
+                    .map(|t| t.kind.clone());
+                MessageLine {
+                    kind,
+                    y: range.0,
+                    shape,
+                }
+            })
+            .collect::<Vec<_>>();
+        lines.sort_by_key(|line| line.y);
+        lines
+    }
+}
+
+impl<V, K> Matcher<V> for MessageMatcher<K>
+where
+    V: GenericImageView<Pixel = Bgra<u8>>,
+    K: Clone + Debug,
+{
+    type MatchResult = MessageMatchResult<K>;
+
+    type CandidatesIter<'a> = std::iter::Once<SubImage<&'a V::InnerImageView>> where V: 'a;
+
+    fn view_dimensions(&self) -> (u32, u32) {
+        (0, 0)
+    }
+
+    fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a> {
+        let (w, h) = (view.width() as f64, view.height() as f64);
+        let (x, y) = ((self.region.0 * w) as u32, (self.region.1 * h) as u32);
+        let (rw, rh) = (
+            ((self.region.2 * w) as u32).min(view.width() - x),
+            ((self.region.3 * h) as u32).min(view.height() - y),
+        );
+        std::iter::once(view.view(x, y, rw, rh))
+    }
+
+    fn check<'a>(&self, _view: &SubImage<&'a V>) -> bool {
+        true
+    }
+
+    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        let lines = self.read(view);
+        if lines.is_empty() {
+            return None;
+        }
+        Some(MessageMatchResult { lines })
+    }
+}
+
+/// Turns the lines of consecutive screens into new messages, by skipping the lines which have
+/// only scrolled up since the last screen.
+#[derive(Debug, Default)]
+pub struct MessageTracker {
+    last: Vec<u64>,
+}
+
+impl MessageTracker {
+    /// Lines of the result which were not on the last screen.
+    pub fn push<'a, K>(&mut self, result: &'a MessageMatchResult<K>) -> &'a [MessageLine<K>] {
+        let current = result
+            .lines
+            .iter()
+            .map(|x| x.shape.hash())
+            .collect::<Vec<_>>();
+        let shift = (0..=self.last.len())
+            .find(|&s| current.starts_with(&self.last[s..]))
+            .unwrap();
+        let old = self.last.len() - shift;
+        self.last = current;
+        &result.lines[old..]
+    }
+}
+
+#[test]
+fn recognize_messages() {
+    let white = Bgra([255, 255, 255, 255]);
+    let yellow = Bgra([0, 255, 255, 255]);
+    let render = |img: &mut image::ImageBuffer<Bgra<u8>, Vec<u8>>, y0: u32, text: &str, p| {
+        for (y, line) in text.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if c == '#' {
+                    img.put_pixel(x as u32 + 2, y0 + y as u32, p);
+                }
+            }
+        }
+    };
+    const RUNE: &str = "#.#.##\n###.#.\n#.#.##";
+    const RUNE_LONG: &str = "#.#.##..#\n###.#...#\n#.#.##..#";
+    const DROP: &str = "##..#\n#.#.#\n##..#";
+
+    let mut rune = image::ImageBuffer::from_pixel(10, 5, Bgra([0, 0, 0, 255]));
+    render(&mut rune, 1, RUNE, yellow);
+    let mut drop = image::ImageBuffer::from_pixel(10, 5, Bgra([0, 0, 0, 255]));
+    render(&mut drop, 0, DROP, white);
+    let mut rune_long = image::ImageBuffer::from_pixel(12, 5, Bgra([0, 0, 0, 255]));
+    render(&mut rune_long, 0, RUNE_LONG, yellow);
+    let templates = vec![
+        MessageTemplate::from_image("rune", &rune, ColorKey::new([yellow], 8)).unwrap(),
+        MessageTemplate::from_image("drop", &drop, ColorKey::new([white], 8)).unwrap(),
+        // Shares the prefix with "rune"
+        MessageTemplate::from_image("rune_long", &rune_long, ColorKey::new([yellow], 8)).unwrap(),
+    ];
+    let matcher = MessageMatcher::new(templates, CHAT_REGION);
+
+    let mut screen = image::ImageBuffer::from_pixel(20, 16, Bgra([30, 20, 10, 255]));
+    render(&mut screen, 1, DROP, white);
+    render(&mut screen, 6, RUNE_LONG, yellow);
+    render(&mut screen, 11, RUNE, yellow);
+    let lines = matcher.read(&screen);
+    assert_eq!(
+        lines.iter().map(|x| x.kind).collect::<Vec<_>>(),
+        [Some("drop"), Some("rune_long"), Some("rune")]
+    );
+    let lines = lines[..2].to_vec();
+
+    let mut tracker = MessageTracker::default();
+    let result = MessageMatchResult { lines };
+    assert_eq!(tracker.push(&result).len(), 2);
+    assert_eq!(tracker.push(&result).len(), 0);
+    let scrolled = MessageMatchResult {
+        lines: vec![result.lines[1].clone(), result.lines[0].clone()],
+    };
+    assert_eq!(tracker.push(&scrolled).len(), 1);
+}
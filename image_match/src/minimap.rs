@@ -0,0 +1,368 @@
+use assets_embedded::assets;
+use assets_manager::asset::Png;
+use image::{Bgra, GenericImageView, ImageBuffer, SubImage};
+use once_cell::sync::OnceCell;
+
+use crate::{GenericImageViewExt, Matcher};
+
+/// Entities shown as icons on the minimap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MinimapEntity {
+    Rune,
+    EliteBoss,
+    SpecialPortal,
+}
+
+impl MinimapEntity {
+    pub const ALL: [MinimapEntity; 3] = [
+        MinimapEntity::Rune,
+        MinimapEntity::EliteBoss,
+        MinimapEntity::SpecialPortal,
+    ];
+
+    fn asset(self) -> &'static str {
+        match self {
+            MinimapEntity::Rune => "minimap.rune",
+            MinimapEntity::EliteBoss => "minimap.elite_boss",
+            MinimapEntity::SpecialPortal => "minimap.special_portal",
+        }
+    }
+
+    /// Whether the icon of the entity is loaded.
+    pub fn available(self) -> bool {
+        Self::ALL
+            .iter()
+            .position(|&x| x == self)
+            .and_then(|i| MINIMAP_ICONS.get()?[i].as_ref())
+            .is_some()
+    }
+}
+
+/// Locates the minimap on the top left of the screen, and finds the entities on it.
+///
+/// The minimap is located with its corner templates `assets/minimap/top_left.png` and
+/// `assets/minimap/bottom_right.png`. Without them the matcher never matches; check
+/// [`MinimapMatcher::available`]. Entity icons are read from `assets/minimap/` as well, and
+/// entities without an icon are never found.
+#[derive(Clone)]
+pub struct MinimapMatcher;
+
+#[derive(Debug, Clone)]
+pub struct MinimapMatchResult {
+    bounds: (u32, u32, u32, u32),
+    entities: Vec<(MinimapEntity, (u32, u32))>,
+}
+
+impl MinimapMatchResult {
+    /// `(x, y, width, height)` of the minimap content on the screen, excluding the frame.
+    pub fn bounds(&self) -> (u32, u32, u32, u32) {
+        self.bounds
+    }
+
+    /// Entities and their positions relative to the top left of the minimap content.
+    pub fn entities(&self) -> &[(MinimapEntity, (u32, u32))] {
+        &self.entities
+    }
+
+    pub fn contains(&self, entity: MinimapEntity) -> bool {
+        self.entities.iter().any(|(x, _)| *x == entity)
+    }
+}
+
+type Icon = ImageBuffer<Bgra<u8>, Vec<u8>>;
+
+static MINIMAP_CORNERS: OnceCell<Option<(Icon, Icon)>> = OnceCell::new();
+static MINIMAP_ICONS: OnceCell<[Option<Icon>; 3]> = OnceCell::new();
+
+/// The minimap is never larger than this.
+const MINIMAP_SEARCH: (u32, u32) = (640, 480);
+/// How far the minimap can be from the top left corner of the screen.
+const MINIMAP_MAX_OFFSET: u32 = 16;
+
+fn load(id: &str) -> Option<Icon> {
+    Some(assets().load::<Png>(id).ok()?.cloned().0.to_bgra8())
+}
+
+/// Compares the opaque pixels of the icon against the view at `(x, y)`.
+fn icon_at<I>(view: &I, icon: &Icon, x: u32, y: u32) -> bool
+where
+    I: GenericImageView<Pixel = Bgra<u8>>,
+{
+    icon.enumerate_pixels()
+        .filter(|(_, _, p)| p.0[3] == 255)
+        .all(|(ix, iy, p)| view.get_pixel(x + ix, y + iy) == *p)
+}
+
+/// Finds every position of the icon in the view, skipping overlapping ones.
+fn find_icons<I>(view: &I, icon: &Icon) -> Vec<(u32, u32)>
+where
+    I: GenericImageView<Pixel = Bgra<u8>>,
+{
+    if view.width() < icon.width() || view.height() < icon.height() {
+        return Vec::new();
+    }
+
+    let mut found: Vec<(u32, u32)> = Vec::new();
+    for y in 0..=view.height() - icon.height() {
+        for x in 0..=view.width() - icon.width() {
+            let overlaps = found.iter().any(|&(fx, fy)| {
+                x < fx + icon.width() && fx < x + icon.width() && y < fy + icon.height()
+            });
+            if !overlaps && icon_at(view, icon, x, y) {
+                found.push((x, y));
+            }
+        }
+    }
+    found
+}
+
+impl MinimapMatcher {
+    pub fn available() -> bool {
+        matches!(MINIMAP_CORNERS.get(), Some(Some(_)))
+    }
+
+    /// `(x, y, width, height)` of the minimap content in the view.
+    fn locate<I>(view: &I) -> Option<(u32, u32, u32, u32)>
+    where
+        I: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        let (top_left, bottom_right) = MINIMAP_CORNERS.get()?.as_ref()?;
+        let (tw, th) = top_left.dimensions();
+        let (bw, bh) = bottom_right.dimensions();
+        if view.width() < tw + bw || view.height() < th + bh {
+            return None;
+        }
+
+        let (x0, y0) = (0..=MINIMAP_MAX_OFFSET.min(view.height() - th))
+            .flat_map(|y| (0..=MINIMAP_MAX_OFFSET.min(view.width() - tw)).map(move |x| (x, y)))
+            .find(|&(x, y)| view.view(x, y, tw, th).eq(top_left))?;
+        let (x1, y1) = (y0 + th..=view.height() - bh)
+            .flat_map(|y| (x0 + tw..=view.width() - bw).map(move |x| (x, y)))
+            .find(|&(x, y)| view.view(x, y, bw, bh).eq(bottom_right))?;
+
+        Some((x0 + tw, y0 + th, x1 - x0 - tw, y1 - y0 - th))
+    }
+}
+
+impl<V: GenericImageView<Pixel = Bgra<u8>>> Matcher<V> for MinimapMatcher {
+    type MatchResult = MinimapMatchResult;
+
+    type CandidatesIter<'a> = impl Iterator<Item = SubImage<&'a V::InnerImageView>> + 'a where V: 'a;
+
+    fn init() {
+        MINIMAP_CORNERS
+            .get_or_init(|| Some((load("minimap.top_left")?, load("minimap.bottom_right")?)));
+        MINIMAP_ICONS.get_or_init(|| MinimapEntity::ALL.map(|x| load(x.asset())));
+    }
+
+    fn view_dimensions(&self) -> (u32, u32) {
+        MINIMAP_SEARCH
+    }
+
+    fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a> {
+        let (w, h) = (
+            MINIMAP_SEARCH.0.min(view.width()),
+            MINIMAP_SEARCH.1.min(view.height()),
+        );
+        std::iter::once(view.view(0, 0, w, h)).filter(|_| Self::available())
+    }
+
+    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        let (x, y, w, h) = Self::locate(view)?;
+        let minimap = view.view(x, y, w, h);
+        let entities = MinimapEntity::ALL
+            .iter()
+            .zip(MINIMAP_ICONS.get()?)
+            .filter_map(|(entity, icon)| Some((*entity, icon.as_ref()?)))
+            .flat_map(|(entity, icon)| {
+                find_icons(&minimap, icon)
+                    .into_iter()
+                    .map(move |pos| (entity, pos))
+            })
+            .collect();
+
+        let (ox, oy, _, _) = view.bounds();
+        Some(MinimapMatchResult {
+            bounds: (ox + x, oy + y, w, h),
+            entities,
+        })
+    }
+}
+
+/// Finds the dots of the player and other players on the minimap.
+///
+/// Uses the same corner templates as [`MinimapMatcher`]; check [`MinimapMatcher::available`].
+#[derive(Clone)]
+pub struct MinimapPlayerMatcher;
+
+#[derive(Debug, Clone)]
+pub struct MinimapPlayerMatchResult {
+    /// `(width, height)` of the minimap content.
+    size: (u32, u32),
+    player: Option<(f64, f64)>,
+    others: Vec<(f64, f64)>,
+}
+
+impl MinimapPlayerMatchResult {
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Position of the player, normalized to `0.0..=1.0` on each axis of the minimap.
+    pub fn player(&self) -> Option<(f64, f64)> {
+        self.player
+    }
+
+    /// Normalized positions of other players.
+    pub fn others(&self) -> &[(f64, f64)] {
+        &self.others
+    }
+}
+
+fn is_player_dot(p: Bgra<u8>) -> bool {
+    let [b, g, r, _] = p.0;
+    r >= 220 && g >= 200 && b < 80
+}
+
+fn is_other_dot(p: Bgra<u8>) -> bool {
+    let [b, g, r, _] = p.0;
+    r >= 200 && g < 60 && b < 60
+}
+
+/// Centers of the clusters of pixels satisfying the predicate, normalized by the view size. The
+/// biggest cluster comes first.
+fn find_dots<I>(view: &I, pred: impl Fn(Bgra<u8>) -> bool) -> Vec<(f64, f64)>
+where
+    I: GenericImageView<Pixel = Bgra<u8>>,
+{
+    /// Pixels this close to a cluster belong to it.
+    const GAP: u32 = 2;
+
+    // (min_x, min_y, max_x, max_y, sum_x, sum_y, count)
+    let mut clusters: Vec<(u32, u32, u32, u32, u64, u64, u64)> = Vec::new();
+    for (x, y, p) in view.pixels() {
+        if !pred(p) {
+            continue;
+        }
+        let cluster = clusters
+            .iter_mut()
+            .find(|c| x + GAP >= c.0 && x <= c.2 + GAP && y + GAP >= c.1 && y <= c.3 + GAP);
+        match cluster {
+            Some(c) => {
+                *c = (
+                    c.0.min(x),
+                    c.1.min(y),
+                    c.2.max(x),
+                    c.3.max(y),
+                    c.4 + x as u64,
+                    c.5 + y as u64,
+                    c.6 + 1,
+                )
+            }
+            None => clusters.push((x, y, x, y, x as u64, y as u64, 1)),
+        }
+    }
+
+    clusters.sort_by_key(|c| std::cmp::Reverse(c.6));
+    let (w, h) = (view.width().max(1) as f64, view.height().max(1) as f64);
+    clusters
+        .into_iter()
+        .map(|c| (c.4 as f64 / c.6 as f64 / w, c.5 as f64 / c.6 as f64 / h))
+        .collect()
+}
+
+impl<V: GenericImageView<Pixel = Bgra<u8>>> Matcher<V> for MinimapPlayerMatcher {
+    type MatchResult = MinimapPlayerMatchResult;
+
+    type CandidatesIter<'a> = <MinimapMatcher as Matcher<V>>::CandidatesIter<'a> where V: 'a;
+
+    fn init() {
+        <MinimapMatcher as Matcher<V>>::init();
+    }
+
+    fn view_dimensions(&self) -> (u32, u32) {
+        MINIMAP_SEARCH
+    }
+
+    fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a> {
+        Matcher::<V>::candidates_iter(&MinimapMatcher, view)
+    }
+
+    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        let (x, y, w, h) = MinimapMatcher::locate(view)?;
+        let minimap = view.view(x, y, w, h);
+        Some(MinimapPlayerMatchResult {
+            size: (w, h),
+            // Take the biggest one if there are more, e.g. from a misread
+            player: find_dots(&minimap, is_player_dot).into_iter().next(),
+            others: find_dots(&minimap, is_other_dot),
+        })
+    }
+}
+
+#[cfg(test)]
+fn test_icon(w: u32, h: u32, seed: u8) -> Icon {
+    ImageBuffer::from_fn(w, h, |x, y| {
+        Bgra([
+            seed.wrapping_add(x as u8 * 40),
+            seed.wrapping_mul(3).wrapping_add(y as u8 * 50),
+            100,
+            255,
+        ])
+    })
+}
+
+#[test]
+fn minimap_corners_and_icons() {
+    let (top_left, bottom_right) = (test_icon(6, 5, 1), test_icon(4, 4, 2));
+    let rune = test_icon(3, 3, 7);
+    MINIMAP_CORNERS.get_or_init(|| Some((top_left.clone(), bottom_right.clone())));
+    MINIMAP_ICONS.get_or_init(|| [Some(rune.clone()), None, None]);
+
+    let mut screen = ImageBuffer::from_pixel(800, 600, Bgra([0, 0, 0, 255]));
+    // The minimap content spans 100x60 pixels from (13, 9)
+    image::imageops::replace(&mut screen, &top_left, 7, 4);
+    image::imageops::replace(&mut screen, &bottom_right, 113, 69);
+    image::imageops::replace(&mut screen, &rune, 13 + 20, 9 + 30);
+    image::imageops::replace(&mut screen, &rune, 13 + 70, 9 + 10);
+    // Outside of the minimap
+    image::imageops::replace(&mut screen, &rune, 300, 300);
+
+    let result = MinimapMatcher.find(&screen).unwrap();
+    assert_eq!(result.bounds(), (13, 9, 100, 60));
+    assert_eq!(
+        result.entities(),
+        [
+            (MinimapEntity::Rune, (70, 10)),
+            (MinimapEntity::Rune, (20, 30)),
+        ]
+    );
+    assert!(!result.contains(MinimapEntity::EliteBoss));
+
+    let mut player = screen.clone();
+    player.put_pixel(13 + 50, 9 + 30, Bgra([0, 230, 250, 255]));
+    player.put_pixel(13 + 51, 9 + 30, Bgra([0, 230, 250, 255]));
+    let result = MinimapPlayerMatcher.find(&player).unwrap();
+    assert_eq!(result.size(), (100, 60));
+    assert_eq!(result.player(), Some((0.505, 0.5)));
+
+    // Too far from the top left corner of the screen
+    let mut moved = ImageBuffer::from_pixel(800, 600, Bgra([0, 0, 0, 255]));
+    image::imageops::replace(&mut moved, &top_left, 7 + MINIMAP_MAX_OFFSET, 4);
+    image::imageops::replace(&mut moved, &bottom_right, 113 + MINIMAP_MAX_OFFSET, 69);
+    assert!(MinimapMatcher.find(&moved).is_none());
+}
+
+#[test]
+fn minimap_overlapping_icons() {
+    let icon = ImageBuffer::from_pixel(3, 3, Bgra([0, 0, 255, 255]));
+    let mut minimap = ImageBuffer::from_pixel(20, 10, Bgra([0, 0, 0, 255]));
+    // Two icons side by side, where a 3x3 window matches at every column of the 6x3 block
+    for x in 2..8 {
+        for y in 4..7 {
+            minimap.put_pixel(x, y, Bgra([0, 0, 255, 255]));
+        }
+    }
+    assert_eq!(find_icons(&minimap, &icon), [(2, 4), (5, 4)]);
+    assert!(find_icons(&minimap.view(0, 0, 2, 2), &icon).is_empty());
+}
@@ -0,0 +1,339 @@
+//! Reading numbers rendered with MapleStory's fixed bitmap fonts.
+//!
+//! Text pixels are separated from the background with a [`ColorKey`], split into glyphs by empty
+//! columns, and each glyph is compared against the glyphs of a [`BitmapFont`].
+
+use assets_embedded::assets;
+use assets_manager::asset::Png;
+use image::{Bgra, GenericImageView, ImageBuffer};
+
+/// Pixels close enough to any of the colors are treated as text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorKey {
+    pub colors: Vec<Bgra<u8>>,
+    /// Maximum difference per channel.
+    pub tolerance: u8,
+}
+
+impl ColorKey {
+    pub fn new(colors: impl IntoIterator<Item = Bgra<u8>>, tolerance: u8) -> Self {
+        Self {
+            colors: colors.into_iter().collect(),
+            tolerance,
+        }
+    }
+
+    pub fn contains(&self, p: Bgra<u8>) -> bool {
+        self.colors.iter().any(|c| {
+            c.0.iter()
+                .zip(p.0.iter())
+                .take(3)
+                .all(|(x, y)| x.abs_diff(*y) <= self.tolerance)
+        })
+    }
+}
+
+/// Binary mask of text pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Mask {
+    width: u32,
+    height: u32,
+    bits: Vec<bool>,
+}
+
+impl Mask {
+    fn from_fn(width: u32, height: u32, f: impl Fn(u32, u32) -> bool) -> Self {
+        Self {
+            width,
+            height,
+            bits: (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| f(x, y))
+                .collect(),
+        }
+    }
+
+    fn get(&self, x: u32, y: u32) -> bool {
+        x < self.width && y < self.height && self.bits[(y * self.width + x) as usize]
+    }
+
+    fn column_empty(&self, x: u32) -> bool {
+        (0..self.height).all(|y| !self.get(x, y))
+    }
+
+    /// Runs of non-empty columns as `(start, end)` pairs, `end` being exclusive.
+    fn column_runs(&self) -> Vec<(u32, u32)> {
+        let mut runs = Vec::new();
+        let mut start = None;
+        for x in 0..=self.width {
+            match (start, x < self.width && !self.column_empty(x)) {
+                (None, true) => start = Some(x),
+                (Some(s), false) => {
+                    runs.push((s, x));
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+        runs
+    }
+
+    /// Rows of the line of text as `(top, bottom)`, `bottom` being exclusive. Empty rows above and
+    /// below every glyph are left out.
+    fn line_rows(&self) -> (u32, u32) {
+        let row_empty = |y| (0..self.width).all(|x| !self.get(x, y));
+        let top = (0..self.height).find(|&y| !row_empty(y)).unwrap_or(0);
+        let bottom = (0..self.height)
+            .rev()
+            .find(|&y| !row_empty(y))
+            .map_or(top, |y| y + 1);
+        (top, bottom)
+    }
+
+    /// Crops columns `xs` and rows `ys`. Glyphs are cropped to the rows of the whole line, so that
+    /// glyphs differing only in their vertical position, e.g. `.` and `'`, are told apart.
+    fn crop(&self, xs: (u32, u32), ys: (u32, u32)) -> Self {
+        Self::from_fn(xs.1 - xs.0, ys.1 - ys.0, |x, y| {
+            self.get(x + xs.0, y + ys.0)
+        })
+    }
+
+    /// Ratio of agreeing pixels when the two masks are aligned at the top left corner.
+    fn similarity(&self, other: &Mask) -> f64 {
+        let (w, h) = (self.width.max(other.width), self.height.max(other.height));
+        if w == 0 || h == 0 {
+            return 0.0;
+        }
+        let agree = (0..h)
+            .flat_map(|y| (0..w).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.get(x, y) == other.get(x, y))
+            .count();
+        agree as f64 / (w * h) as f64
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Glyph {
+    ch: char,
+    mask: Mask,
+}
+
+/// Glyph shapes of a bitmap font.
+#[derive(Debug, Clone)]
+pub struct BitmapFont {
+    glyphs: Vec<Glyph>,
+}
+
+impl BitmapFont {
+    /// Builds a font from a glyph sheet: glyphs of `charset` placed left to right in order,
+    /// separated by fully transparent columns. Opaque pixels are the text pixels.
+    ///
+    /// Returns `None` if the number of glyphs in the sheet does not match the charset.
+    pub fn from_sheet(sheet: &ImageBuffer<Bgra<u8>, Vec<u8>>, charset: &str) -> Option<Self> {
+        let mask = Mask::from_fn(sheet.width(), sheet.height(), |x, y| {
+            sheet.get_pixel(x, y).0[3] == 255
+        });
+        let runs = mask.column_runs();
+        if runs.len() != charset.chars().count() {
+            return None;
+        }
+        let rows = mask.line_rows();
+
+        Some(Self {
+            glyphs: charset
+                .chars()
+                .zip(runs)
+                .map(|(ch, run)| Glyph {
+                    ch,
+                    mask: mask.crop(run, rows),
+                })
+                .collect(),
+        })
+    }
+
+    /// Loads a glyph sheet from the embedded assets. See [`BitmapFont::from_sheet`].
+    pub fn from_asset(id: &str, charset: &str) -> Option<Self> {
+        let sheet = assets().load::<Png>(id).ok()?.cloned().0.to_bgra8();
+        Self::from_sheet(&sheet, charset)
+    }
+
+    fn max_glyph_width(&self) -> u32 {
+        self.glyphs.iter().map(|g| g.mask.width).max().unwrap_or(0)
+    }
+
+    fn best_glyph(&self, mask: &Mask) -> Option<(&Glyph, f64)> {
+        self.glyphs
+            .iter()
+            .map(|g| (g, g.mask.similarity(mask)))
+            .max_by(|x, y| x.1.partial_cmp(&y.1).unwrap_or(std::cmp::Ordering::Equal))
+    }
+
+    /// Reads a line of text in the view. Pixels matching `key` are treated as text.
+    pub fn read<V>(&self, view: &V, key: &ColorKey) -> OcrResult
+    where
+        V: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        let mask = Mask::from_fn(view.width(), view.height(), |x, y| {
+            key.contains(view.get_pixel(x, y))
+        });
+
+        let rows = mask.line_rows();
+        let max_width = self.max_glyph_width();
+        let mut glyphs = Vec::new();
+        for (start, end) in mask.column_runs() {
+            // Glyphs may touch each other, so split wide runs greedily with the best fitting glyph.
+            let mut x = start;
+            while x < end {
+                let best = if end - x > max_width {
+                    self.glyphs
+                        .iter()
+                        .map(|g| {
+                            let width = g.mask.width.min(end - x);
+                            (
+                                g,
+                                g.mask.similarity(&mask.crop((x, x + width), rows)),
+                                width,
+                            )
+                        })
+                        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+                } else {
+                    self.best_glyph(&mask.crop((x, end), rows))
+                        .map(|(g, confidence)| (g, confidence, end - x))
+                };
+
+                let (glyph, confidence, width) = match best {
+                    Some(best) => best,
+                    None => break,
+                };
+                glyphs.push(OcrGlyph {
+                    ch: glyph.ch,
+                    confidence,
+                    x,
+                });
+                x += width.max(1);
+            }
+        }
+
+        OcrResult { glyphs }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OcrGlyph {
+    pub ch: char,
+    /// Ratio of pixels agreeing with the font glyph, in `0.0..=1.0`.
+    pub confidence: f64,
+    /// X coordinate of the glyph in the view.
+    pub x: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OcrResult {
+    pub glyphs: Vec<OcrGlyph>,
+}
+
+impl OcrResult {
+    pub fn text(&self) -> String {
+        self.glyphs.iter().map(|g| g.ch).collect()
+    }
+
+    /// Confidence of the least confident glyph.
+    pub fn confidence(&self) -> f64 {
+        self.glyphs
+            .iter()
+            .map(|g| g.confidence)
+            .fold(f64::NAN, f64::min)
+    }
+
+    /// Drops the result if any glyph is less confident than `threshold`.
+    pub fn confident(self, threshold: f64) -> Option<Self> {
+        if !self.glyphs.is_empty() && self.confidence() >= threshold {
+            Some(self)
+        } else {
+            None
+        }
+    }
+
+    /// Groups of consecutive digits, e.g. `[12, 34]` for `12:34`.
+    pub fn numbers(&self) -> Vec<u32> {
+        self.text()
+            .split(|c: char| !c.is_ascii_digit())
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| s.parse().ok())
+            .collect()
+    }
+
+    /// The number if the text consists of a single group of digits.
+    pub fn number(&self) -> Option<u32> {
+        match self.numbers()[..] {
+            [n] => Some(n),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn read_digits() {
+    const GLYPHS: [&str; 3] = ["###\n..#\n..#", "###\n#.#\n###", ".\n#\n#"];
+    let render = |img: &mut ImageBuffer<Bgra<u8>, Vec<u8>>, x0: u32, glyph: &str, p: Bgra<u8>| {
+        for (y, line) in glyph.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if c == '#' {
+                    img.put_pixel(x0 + x as u32, y as u32 + 1, p);
+                }
+            }
+        }
+    };
+
+    let mut sheet = ImageBuffer::new(12, 5);
+    render(&mut sheet, 0, GLYPHS[0], Bgra([0, 0, 0, 255]));
+    render(&mut sheet, 4, GLYPHS[1], Bgra([0, 0, 0, 255]));
+    render(&mut sheet, 8, GLYPHS[2], Bgra([0, 0, 0, 255]));
+    let font = BitmapFont::from_sheet(&sheet, "70:").unwrap();
+
+    let white = Bgra([255, 255, 255, 255]);
+    let mut screen = ImageBuffer::from_pixel(20, 5, Bgra([40, 30, 20, 255]));
+    render(&mut screen, 1, GLYPHS[0], white);
+    render(&mut screen, 4, GLYPHS[1], white);
+    render(&mut screen, 8, GLYPHS[2], white);
+    render(&mut screen, 10, GLYPHS[1], white);
+    render(&mut screen, 14, GLYPHS[0], white);
+
+    let result = font.read(&screen, &ColorKey::new([white], 8));
+    assert_eq!(result.text(), "70:07");
+    assert_eq!(result.numbers(), vec![70, 7]);
+    assert_eq!(result.number(), None);
+    assert!(result.confidence() > 0.99);
+}
+
+#[test]
+fn read_glyphs_by_position() {
+    // A comma and an apostrophe only differ in their position in the line
+    const GLYPHS: [&str; 3] = ["#\n#\n#", ".\n.\n#", "#\n.\n."];
+    let render = |img: &mut ImageBuffer<Bgra<u8>, Vec<u8>>, x0: u32, glyph: &str, p: Bgra<u8>| {
+        for (y, line) in glyph.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if c == '#' {
+                    img.put_pixel(x0 + x as u32, y as u32 + 1, p);
+                }
+            }
+        }
+    };
+
+    let mut sheet = ImageBuffer::new(6, 5);
+    for (i, glyph) in GLYPHS.iter().enumerate() {
+        render(&mut sheet, i as u32 * 2, glyph, Bgra([0, 0, 0, 255]));
+    }
+    let font = BitmapFont::from_sheet(&sheet, "1,'").unwrap();
+
+    let white = Bgra([255, 255, 255, 255]);
+    let mut screen = ImageBuffer::from_pixel(8, 5, Bgra([40, 30, 20, 255]));
+    render(&mut screen, 0, GLYPHS[0], white);
+    render(&mut screen, 2, GLYPHS[2], white);
+    render(&mut screen, 4, GLYPHS[1], white);
+    render(&mut screen, 6, GLYPHS[0], white);
+
+    let result = font.read(&screen, &ColorKey::new([white], 8));
+    assert_eq!(result.text(), "1',1");
+    assert!(result.confidence() > 0.99);
+}
@@ -0,0 +1,278 @@
+//! Perceptual hashes of icons, and an index to find the icons nearest to a screen cell.
+//!
+//! Comparing a cell against every icon of a large catalog with NCC is slow. [`IconIndex`] narrows
+//! the catalog down to a few candidates by the Hamming distance of 64-bit difference hashes, which
+//! are then verified by the usual matchers.
+
+use image::{Bgra, GenericImageView, ImageBuffer};
+
+/// Icons are hashed on a grid of this many cells. Each bit compares horizontally adjacent cells.
+const GRID: (u32, u32) = (9, 8);
+
+/// Difference hash of the view: the luminance of a 9x8 grid of cells, where each bit tells whether
+/// a cell is brighter than the cell on its right. Pixels outside of the mask are skipped.
+pub fn dhash<I>(view: &I, mask: &[bool]) -> u64
+where
+    I: GenericImageView<Pixel = Bgra<u8>>,
+{
+    let (w, h) = view.dimensions();
+    let mut sums = [(0.0, 0u32); (GRID.0 * GRID.1) as usize];
+    for (x, y, p) in view.pixels() {
+        if !mask[(y * w + x) as usize] {
+            continue;
+        }
+        let cell = (y * GRID.1 / h * GRID.0 + x * GRID.0 / w) as usize;
+        let [b, g, r, _] = p.0;
+        sums[cell].0 += 0.114 * b as f64 + 0.587 * g as f64 + 0.299 * r as f64;
+        sums[cell].1 += 1;
+    }
+
+    // Cells without a pixel in the mask take the mean, so they only depend on their neighbors
+    let (total, count) = sums
+        .iter()
+        .fold((0.0, 0), |(t, c), (sum, n)| (t + sum, c + n));
+    let mean = total / count.max(1) as f64;
+    let cells = sums
+        .iter()
+        .map(|&(sum, n)| if n == 0 { mean } else { sum / n as f64 })
+        .collect::<Vec<_>>();
+
+    let mut hash = 0;
+    for y in 0..GRID.1 {
+        for x in 0..GRID.0 - 1 {
+            let i = (y * GRID.0 + x) as usize;
+            hash = hash << 1 | (cells[i] > cells[i + 1]) as u64;
+        }
+    }
+    hash
+}
+
+pub fn hamming(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    hash: u64,
+    value: T,
+    /// Children keyed by their distance from this node.
+    children: Vec<(u32, Node<T>)>,
+}
+
+/// BK-tree of hashes under the Hamming distance.
+#[derive(Debug, Clone)]
+pub struct BkTree<T> {
+    root: Option<Node<T>>,
+    len: usize,
+}
+
+impl<T> Default for BkTree<T> {
+    fn default() -> Self {
+        Self { root: None, len: 0 }
+    }
+}
+
+impl<T> BkTree<T> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, hash: u64, value: T) {
+        self.len += 1;
+        let mut node = match &mut self.root {
+            Some(root) => root,
+            None => {
+                self.root = Some(Node {
+                    hash,
+                    value,
+                    children: Vec::new(),
+                });
+                return;
+            }
+        };
+        loop {
+            let d = hamming(node.hash, hash);
+            match node.children.iter().position(|(x, _)| *x == d) {
+                Some(i) => node = &mut node.children[i].1,
+                None => {
+                    node.children.push((
+                        d,
+                        Node {
+                            hash,
+                            value,
+                            children: Vec::new(),
+                        },
+                    ));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// At most `k` values nearest to the hash, with their distances in increasing order.
+    pub fn nearest(&self, hash: u64, k: usize) -> Vec<(u32, &T)> {
+        let mut found = Vec::with_capacity(k + 1);
+        if let Some(root) = &self.root {
+            if k > 0 {
+                Self::search(root, hash, k, &mut found);
+            }
+        }
+        found
+    }
+
+    fn search<'a>(node: &'a Node<T>, hash: u64, k: usize, found: &mut Vec<(u32, &'a T)>) {
+        let d = hamming(node.hash, hash);
+        if found.len() < k || d < found[found.len() - 1].0 {
+            let i = found.partition_point(|(x, _)| *x <= d);
+            found.insert(i, (d, &node.value));
+            found.truncate(k);
+        }
+        for (child_d, child) in &node.children {
+            // By the triangle inequality, the subtree is no closer than `|child_d - d|`
+            if found.len() < k || child_d.abs_diff(d) < found[found.len() - 1].0 {
+                Self::search(child, hash, k, found);
+            }
+        }
+    }
+}
+
+/// Pixels along the edges of an icon left out of the hashes, where the frame of the slot is drawn.
+const FRAME: u32 = 1;
+
+/// Index of icons of the same size by their difference hashes.
+///
+/// Icons are hashed as drawn over a black background, so that their shapes are hashed as on the
+/// screen.
+#[derive(Debug, Clone)]
+pub struct IconIndex<K> {
+    dims: (u32, u32),
+    /// Pixels inside the frame.
+    mask: Vec<bool>,
+    tree: BkTree<K>,
+}
+
+impl<K> IconIndex<K> {
+    /// Returns `None` if there is no icon or the icons differ in size.
+    pub fn new(
+        icons: impl IntoIterator<Item = (K, ImageBuffer<Bgra<u8>, Vec<u8>>)>,
+    ) -> Option<Self> {
+        let icons = icons.into_iter().collect::<Vec<_>>();
+        let dims = icons.first()?.1.dimensions();
+        if icons.iter().any(|(_, icon)| icon.dimensions() != dims) {
+            return None;
+        }
+
+        let mask = (0..dims.0 * dims.1)
+            .map(|i| {
+                let (x, y) = (i % dims.0, i / dims.0);
+                (FRAME..dims.0.saturating_sub(FRAME)).contains(&x)
+                    && (FRAME..dims.1.saturating_sub(FRAME)).contains(&y)
+            })
+            .collect::<Vec<_>>();
+        let mut tree = BkTree::default();
+        for (kind, icon) in icons {
+            let drawn = ImageBuffer::from_fn(dims.0, dims.1, |x, y| {
+                let [b, g, r, a] = icon.get_pixel(x, y).0;
+                let blend = |c: u8| (c as u32 * a as u32 / 255) as u8;
+                Bgra([blend(b), blend(g), blend(r), 255])
+            });
+            tree.insert(dhash(&drawn, &mask), kind);
+        }
+        Some(Self { dims, mask, tree })
+    }
+
+    pub fn dims(&self) -> (u32, u32) {
+        self.dims
+    }
+
+    pub fn hash<I>(&self, view: &I) -> u64
+    where
+        I: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        assert_eq!(view.dimensions(), self.dims);
+        dhash(view, &self.mask)
+    }
+
+    /// At most `k` icons nearest to the view, with their distances in increasing order.
+    pub fn nearest<I>(&self, view: &I, k: usize) -> Vec<(u32, &K)>
+    where
+        I: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        self.tree.nearest(self.hash(view), k)
+    }
+}
+
+#[test]
+fn bk_tree_nearest() {
+    let hashes = [0u64, 0b1, 0b11, 0b111, 0xff, 0xffff, u64::MAX, 0b1010];
+    let mut tree = BkTree::default();
+    for (i, hash) in hashes.iter().enumerate() {
+        tree.insert(*hash, i);
+    }
+    assert_eq!(tree.len(), hashes.len());
+
+    for query in [0u64, 0b110, 0xf0f0, u64::MAX - 1] {
+        let mut expected = hashes
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (hamming(*x, query), i))
+            .collect::<Vec<_>>();
+        expected.sort();
+        let found = tree.nearest(query, 3);
+        assert_eq!(
+            found.iter().map(|x| x.0).collect::<Vec<_>>(),
+            expected[..3].iter().map(|x| x.0).collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn icon_index_finds_icon() {
+    let icons = (0..40u32)
+        .map(|i| {
+            let icon = ImageBuffer::from_fn(32, 32, |x, y| {
+                let v = ((x * (i % 5 + 1) + y * (i / 5 + 1)) * 8 % 256) as u8;
+                // Each icon has transparent pixels of its own, so no pixel is opaque in every icon
+                let a = if (x + y * 3 + i) % 7 == 0 { 0 } else { 255 };
+                Bgra([v, v / 2, 255 - v, a])
+            });
+            (i, icon)
+        })
+        .collect::<Vec<_>>();
+    let index = IconIndex::new(icons.clone()).unwrap();
+
+    // Slightly darker, as on the screen with a dark background
+    let cell = |icon: &ImageBuffer<Bgra<u8>, Vec<u8>>, overlay: u32| {
+        ImageBuffer::from_fn(32, 32, |x, y| {
+            let p = icon.get_pixel(x, y).0;
+            let shade = (if x < overlay { 4 } else { 9 }) * p[3] as u32 / 255;
+            let p = p.map(|c| (c as u32 * shade / 10) as u8);
+            Bgra([p[0], p[1], p[2], 255])
+        })
+    };
+    for (i, icon) in &icons {
+        let nearest = index.nearest(&cell(icon, 0), 3);
+        assert!(nearest.iter().any(|(_, x)| *x == i));
+    }
+
+    // A round icon, and one partly covered by the expiration overlay
+    let mut icons = icons;
+    for (x, y, p) in icons[0].1.enumerate_pixels_mut() {
+        if (x as i32 - 16).pow(2) + (y as i32 - 16).pow(2) > 14 * 14 {
+            p.0[3] = 0;
+        }
+    }
+    let index = IconIndex::new(icons.clone()).unwrap();
+    assert!(index
+        .nearest(&cell(&icons[0].1, 0), 3)
+        .iter()
+        .any(|(_, x)| **x == 0));
+    assert!(index
+        .nearest(&cell(&icons[7].1, 12), 3)
+        .iter()
+        .any(|(_, x)| **x == 7));
+}
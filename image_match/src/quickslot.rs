@@ -0,0 +1,274 @@
+use std::{cell::Cell, time::Duration};
+
+use image::{Bgra, GenericImageView, ImageBuffer, SubImage};
+use once_cell::sync::OnceCell;
+
+use crate::{
+    buff::BuffMatcher,
+    ocr::{BitmapFont, ColorKey},
+    GenericImageViewExt, Matcher,
+};
+
+/// Screen dimensions, and the offset of the slot grid on the screen.
+type SlotGrid = ((u32, u32), (u32, u32));
+
+/// Position of a pixel of the icon, and its chromaticity.
+type ChromaSample = ((u32, u32), [f64; 3]);
+
+/// Bounds of a view, and the dark ratio of the icon on it.
+type CheckedView = ((u32, u32, u32, u32), f64);
+
+/// Finds a skill icon on the quickslot panel and reads its cooldown.
+///
+/// The icon is found regardless of its cooldown state, since the grey overlay only darkens it. The
+/// remaining seconds drawn over the icon are read if the glyph sheet `assets/fonts/cooldown.png`
+/// (`0123456789`) is available.
+#[derive(Clone)]
+pub struct QuickslotMatcher {
+    icon: BuffMatcher,
+    /// Colorful pixels of the icon, compared before the icon itself to reject most positions of
+    /// the first search, which is not aligned to the slot grid yet.
+    samples: Vec<ChromaSample>,
+    /// Offset of the slot grid, learned from the first match. Only the slots of the grid are
+    /// searched afterwards.
+    grid: Cell<Option<SlotGrid>>,
+    /// Bounds and dark ratio of the last view passing [`Matcher::check`], taken by
+    /// [`Matcher::match_image`] on the same bounds instead of matching again.
+    checked: Cell<Option<CheckedView>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct QuickslotMatchResult {
+    dark_ratio: f64,
+    seconds: Option<u32>,
+}
+
+impl QuickslotMatchResult {
+    pub fn on_cooldown(&self) -> bool {
+        self.seconds.is_some() || self.dark_ratio > COOLDOWN_DARK_RATIO
+    }
+
+    /// Remaining cooldown of a skill with the cooldown. The number on the icon is used if read,
+    /// and the area of the grey overlay otherwise.
+    pub fn remaining(&self, cooldown: Duration) -> Duration {
+        match self.seconds {
+            Some(seconds) => Duration::from_secs(seconds as u64).min(cooldown),
+            None if self.on_cooldown() => cooldown.mul_f64(self.dark_ratio),
+            None => Duration::ZERO,
+        }
+    }
+}
+
+static COOLDOWN_FONT: OnceCell<Option<BitmapFont>> = OnceCell::new();
+
+/// Icons darker than this are on cooldown. Some pixels are always darker than the template due to
+/// the slot border and the key label.
+const COOLDOWN_DARK_RATIO: f64 = 0.1;
+const QUICKSLOT_SEARCH_HEIGHT: u32 = 100;
+/// Slots of the quickslot panel are laid out on a grid of this pitch.
+const SLOT_SIZE: u32 = 32;
+const MIN_CONFIDENCE: f64 = 0.85;
+const CHROMA_SAMPLES: usize = 16;
+/// Icon pixels dimmer than this have unreliable chromaticity.
+const MIN_SAMPLE_SUM: u32 = 150;
+/// Sampled pixels dimmed below this by the cooldown overlay are not compared.
+const MIN_TARGET_SUM: u32 = 30;
+/// Minimum difference between the largest and the smallest channel ratio of a sampled pixel.
+const MIN_SAMPLE_SPREAD: f64 = 0.1;
+const CHROMA_TOLERANCE: f64 = 0.08;
+
+/// Ratios of the channels to their sum, which the cooldown overlay keeps as it only darkens.
+fn chromaticity(p: Bgra<u8>, min_sum: u32) -> Option<[f64; 3]> {
+    let [b, g, r, _] = p.0;
+    let sum = b as u32 + g as u32 + r as u32;
+    (sum >= min_sum).then(|| {
+        let sum = sum as f64;
+        [b as f64 / sum, g as f64 / sum, r as f64 / sum]
+    })
+}
+
+impl QuickslotMatcher {
+    /// `icon` is the 32x32 skill icon, and `threshold` is passed to [`BuffMatcher`].
+    pub fn new(icon: ImageBuffer<Bgra<u8>, Vec<u8>>, threshold: f64) -> Self {
+        let colorful = icon
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p.0[3] == u8::MAX)
+            .filter_map(|(x, y, p)| Some(((x, y), chromaticity(*p, MIN_SAMPLE_SUM)?)))
+            .filter(|(_, c)| {
+                let (max, min) = c
+                    .iter()
+                    .fold((0f64, 1f64), |(max, min), &x| (max.max(x), min.min(x)));
+                max - min >= MIN_SAMPLE_SPREAD
+            })
+            .collect::<Vec<_>>();
+        let step = (colorful.len() / CHROMA_SAMPLES).max(1);
+        let samples = colorful
+            .into_iter()
+            .step_by(step)
+            .take(CHROMA_SAMPLES)
+            .collect();
+        Self {
+            icon: BuffMatcher::new(icon, threshold, (0, 0)),
+            samples,
+            grid: Cell::new(None),
+            checked: Cell::new(None),
+        }
+    }
+
+    /// Whether most sampled pixels have the chromaticity of the icon. Cheaper than matching the
+    /// icon by far, and tolerates the key label drawn over some of them.
+    fn similar_colors<I: GenericImageView<Pixel = Bgra<u8>>>(&self, view: &I) -> bool {
+        let misses = self
+            .samples
+            .iter()
+            .filter(
+                |((x, y), c)| match chromaticity(view.get_pixel(*x, *y), MIN_TARGET_SUM) {
+                    Some(d) => c
+                        .iter()
+                        .zip(d)
+                        .any(|(c, d)| (c - d).abs() > CHROMA_TOLERANCE),
+                    None => false,
+                },
+            )
+            .count();
+        misses * 4 <= self.samples.len()
+    }
+
+    fn read_seconds<I: GenericImageView<Pixel = Bgra<u8>>>(view: &I) -> Option<u32> {
+        let font = COOLDOWN_FONT.get()?.as_ref()?;
+        let key = ColorKey::new([Bgra([255, 255, 255, 255])], 40);
+        font.read(view, &key).confident(MIN_CONFIDENCE)?.number()
+    }
+}
+
+impl<V: GenericImageView<Pixel = Bgra<u8>>> Matcher<V> for QuickslotMatcher {
+    type MatchResult = QuickslotMatchResult;
+
+    type CandidatesIter<'a> = impl Iterator<Item = SubImage<&'a V::InnerImageView>> + 'a where V: 'a;
+
+    fn init() {
+        COOLDOWN_FONT.get_or_init(|| BitmapFont::from_asset("fonts.cooldown", "0123456789"));
+    }
+
+    fn view_dimensions(&self) -> (u32, u32) {
+        (32, 32)
+    }
+
+    fn candidates_iter<'a>(&self, view: &'a V) -> Self::CandidatesIter<'a> {
+        // The quickslot panel is on the bottom right of the screen
+        let (mut x, mut y) = (
+            view.width() / 2,
+            view.height().saturating_sub(QUICKSLOT_SEARCH_HEIGHT),
+        );
+        let grid = self
+            .grid
+            .get()
+            .filter(|(dims, _)| *dims == view.dimensions())
+            .map(|(_, offset)| offset);
+        let stride = match grid {
+            Some((grid_x, grid_y)) => {
+                x += (grid_x + SLOT_SIZE - x % SLOT_SIZE) % SLOT_SIZE;
+                y += (grid_y + SLOT_SIZE - y % SLOT_SIZE) % SLOT_SIZE;
+                SLOT_SIZE as usize
+            }
+            None => 1,
+        };
+        view.view(x, y, view.width() - x, view.height() - y)
+            .view_bounds_like((32, 32), stride)
+            .map(move |(x, y, w, h)| view.view(x, y, w, h))
+    }
+
+    fn check<'a>(&self, view: &SubImage<&'a V>) -> bool {
+        if !self.similar_colors(view) {
+            return false;
+        }
+        let result = match Matcher::<V>::match_image(&self.icon, view) {
+            Some(result) => result,
+            None => return false,
+        };
+        let (x, y, _, _) = view.bounds();
+        self.grid.set(Some((
+            view.inner().dimensions(),
+            (x % SLOT_SIZE, y % SLOT_SIZE),
+        )));
+        self.checked
+            .set(Some((view.bounds(), 1.0 - result.remaining_ratio())));
+        true
+    }
+
+    fn match_image<'a>(&self, view: &SubImage<&'a V>) -> Option<Self::MatchResult> {
+        let dark_ratio = match self.checked.take() {
+            Some((bounds, dark_ratio)) if bounds == view.bounds() => dark_ratio,
+            _ => 1.0 - Matcher::<V>::match_image(&self.icon, view)?.remaining_ratio(),
+        };
+        Some(QuickslotMatchResult {
+            dark_ratio,
+            seconds: Self::read_seconds(view),
+        })
+    }
+}
+
+#[test]
+fn quickslot_grid() {
+    let icon = ImageBuffer::from_fn(32, 32, |x, y| {
+        Bgra([
+            (x * 8) as u8,
+            ((x * 3 + y * 5) * 3) as u8,
+            (x * y) as u8,
+            255,
+        ])
+    });
+    let matcher = QuickslotMatcher::new(icon.clone(), 0.8);
+    let mut screen = ImageBuffer::from_pixel(1366, 768, Bgra([0, 0, 0, 255]));
+    image::imageops::replace(&mut screen, &icon, 1000, 700);
+    assert!(matcher.find(&screen).is_some());
+
+    // The skill moved to another slot of the panel
+    let mut moved = ImageBuffer::from_pixel(1366, 768, Bgra([0, 0, 0, 255]));
+    image::imageops::replace(&mut moved, &icon, 1000 + SLOT_SIZE * 3, 700 - SLOT_SIZE);
+    let candidates = matcher.candidates_iter(&moved).collect::<Vec<_>>();
+    assert!(candidates.len() < 100);
+    assert!(candidates
+        .iter()
+        .any(|x| x.bounds() == (1000 + SLOT_SIZE * 3, 700 - SLOT_SIZE, 32, 32)));
+    assert!(matcher.find(&moved).is_some());
+}
+
+#[test]
+fn quickslot_first_search() {
+    let icon = ImageBuffer::from_fn(32, 32, |x, y| {
+        Bgra([
+            (x * 8) as u8,
+            ((x * 3 + y * 5) * 3) as u8,
+            (x * y) as u8,
+            255,
+        ])
+    });
+    let matcher = QuickslotMatcher::new(icon.clone(), 0.8);
+    assert_eq!(matcher.samples.len(), CHROMA_SAMPLES);
+
+    // Noisy screen, with the icon half covered by the cooldown overlay
+    let mut seed = 1u32;
+    let mut screen = ImageBuffer::from_fn(1366, 768, |_, _| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        let [b, g, r, _] = seed.to_le_bytes();
+        Bgra([b, g, r, 255])
+    });
+    let mut cooldown = icon;
+    for (_, y, p) in cooldown.enumerate_pixels_mut() {
+        if y < 16 {
+            let [b, g, r, a] = p.0;
+            *p = Bgra([b / 2, g / 2, r / 2, a]);
+        }
+    }
+    image::imageops::replace(&mut screen, &cooldown, 1001, 699);
+
+    let passed = matcher
+        .candidates_iter(&screen)
+        .filter(|x| matcher.similar_colors(x))
+        .count();
+    assert!(passed < 100, "{}", passed);
+    let result = matcher.find(&screen).unwrap();
+    assert!(result.on_cooldown());
+    assert!(matcher.checked.take().is_none());
+}
@@ -0,0 +1,135 @@
+//! Building templates from several screenshots of the same region.
+//!
+//! Each pixel of the template is the per-channel median of the screenshots. Pixels which differ
+//! among the screenshots, e.g. from the background or animations, are unstable and made
+//! transparent so that matchers skip them.
+
+use image::{Bgra, GenericImageView, ImageBuffer, Luma};
+
+use crate::color::Lab;
+
+pub struct Consensus {
+    template: ImageBuffer<Bgra<u8>, Vec<u8>>,
+    /// Largest difference of the stable pixels from the template among the screenshots.
+    max_delta_e: f64,
+    max_channel_diff: u8,
+    unstable: usize,
+}
+
+impl Consensus {
+    /// Pixels differing more than `max_delta_e` from the median in any of the views are unstable.
+    /// Returns `None` if there is no view or the views differ in size.
+    pub fn from_views<I>(views: &[I], max_delta_e: f64) -> Option<Self>
+    where
+        I: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        let (w, h) = views.first()?.dimensions();
+        if views.iter().any(|x| x.dimensions() != (w, h)) {
+            return None;
+        }
+
+        let mut ret = Self {
+            template: ImageBuffer::new(w, h),
+            max_delta_e: 0.0,
+            max_channel_diff: 0,
+            unstable: 0,
+        };
+        let mut samples = Vec::with_capacity(views.len());
+        for (x, y) in (0..h).flat_map(|y| (0..w).map(move |x| (x, y))) {
+            samples.clear();
+            samples.extend(views.iter().map(|v| v.get_pixel(x, y)));
+
+            let mut median = Bgra([0, 0, 0, 255]);
+            for c in 0..3 {
+                let mut channel = samples.iter().map(|p| p.0[c]).collect::<Vec<_>>();
+                channel.sort_unstable();
+                median.0[c] = channel[channel.len() / 2];
+            }
+
+            let lab = Lab::from_bgra(median);
+            let delta_e = samples
+                .iter()
+                .map(|p| Lab::from_bgra(*p).delta_e(&lab))
+                .fold(0.0, f64::max);
+            if delta_e > max_delta_e {
+                median.0[3] = 0;
+                ret.unstable += 1;
+            } else {
+                let channel_diff = samples
+                    .iter()
+                    .flat_map(|p| (0..3).map(move |c| p.0[c].abs_diff(median.0[c])))
+                    .max()
+                    .unwrap_or(0);
+                ret.max_delta_e = ret.max_delta_e.max(delta_e);
+                ret.max_channel_diff = ret.max_channel_diff.max(channel_diff);
+            }
+            ret.template.put_pixel(x, y, median);
+        }
+        Some(ret)
+    }
+
+    /// The template, with unstable pixels transparent.
+    pub fn template(&self) -> &ImageBuffer<Bgra<u8>, Vec<u8>> {
+        &self.template
+    }
+
+    /// White for stable pixels, and black for unstable ones.
+    pub fn mask(&self) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+        ImageBuffer::from_fn(self.template.width(), self.template.height(), |x, y| {
+            Luma([self.template.get_pixel(x, y).0[3]])
+        })
+    }
+
+    pub fn unstable_ratio(&self) -> f64 {
+        self.unstable as f64 / self.template.pixels().len().max(1) as f64
+    }
+
+    /// Largest CIE76 difference of a stable pixel among the screenshots.
+    pub fn max_delta_e(&self) -> f64 {
+        self.max_delta_e
+    }
+
+    /// Largest per-channel difference of a stable pixel among the screenshots.
+    pub fn max_channel_diff(&self) -> u8 {
+        self.max_channel_diff
+    }
+
+    /// Ratio of the stable pixels which are not exactly the same on the view.
+    pub fn mismatch_ratio<I>(&self, view: &I) -> f64
+    where
+        I: GenericImageView<Pixel = Bgra<u8>>,
+    {
+        let (stable, mismatches) = self
+            .template
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p.0[3] == 255)
+            .fold((0usize, 0usize), |(stable, mismatches), (x, y, p)| {
+                (
+                    stable + 1,
+                    mismatches + (view.get_pixel(x, y) != *p) as usize,
+                )
+            });
+        mismatches as f64 / stable.max(1) as f64
+    }
+}
+
+#[test]
+fn consensus_masks_unstable_pixels() {
+    let base = ImageBuffer::from_fn(4, 2, |x, y| {
+        Bgra([(x * 50) as u8, (y * 90) as u8, 120, 255])
+    });
+    let mut noisy = base.clone();
+    noisy.put_pixel(1, 1, Bgra([1, 2, 120, 255]));
+    noisy.put_pixel(2, 0, Bgra([100, 1, 121, 255]));
+
+    let consensus = Consensus::from_views(&[base.clone(), noisy, base.clone()], 2.0).unwrap();
+    assert_eq!(
+        consensus.template().get_pixel(1, 1).0[..3],
+        base.get_pixel(1, 1).0[..3]
+    );
+    assert_eq!(consensus.mask().get_pixel(1, 1), &Luma([0]));
+    assert_eq!(consensus.mask().get_pixel(2, 0), &Luma([255]));
+    assert_eq!(consensus.unstable_ratio(), 1.0 / 8.0);
+    assert_eq!(consensus.max_channel_diff(), 1);
+    assert_eq!(consensus.mismatch_ratio(&base), 0.0);
+}
@@ -0,0 +1,10 @@
+use windows::Win32::{
+    System::Diagnostics::Debug::MessageBeep, UI::WindowsAndMessaging::MB_ICONEXCLAMATION,
+};
+
+/// Plays the system's exclamation sound without blocking.
+pub fn beep() {
+    unsafe {
+        MessageBeep(MB_ICONEXCLAMATION);
+    }
+}
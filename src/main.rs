@@ -1,6 +1,7 @@
 #![cfg_attr(feature = "windows_subsystem", windows_subsystem = "windows")]
 #![allow(clippy::type_complexity)] // TODO
 
+mod alert;
 mod capturer;
 mod fonts;
 mod rw_condvar;
@@ -25,23 +26,56 @@ use fonts::RawFont;
 use image::{Bgra, ImageBuffer, Pixel, RgbaImage};
 use image_match::{
     buff::BuffMatcher,
-    jinhillah::{JinHillahHpMatcher, JinHillahReapMatcher},
+    debug::Outcome,
+    dojang::DojangClearMatcher,
+    exp::ExpMatcher,
+    gauge::PlayerGaugeMatcher,
+    jinhillah::JinHillahReapMatcher,
+    minimap::{MinimapEntity, MinimapMatcher},
+    quickslot::QuickslotMatcher,
     Matcher,
 };
 use log::trace;
 use screen_dimension::ScreenDimension;
 use timers::{
+    cooldown::{CooldownKind, CooldownTimer},
+    debuff::{DebuffAlert, DebuffKind, DebuffTimer},
+    dojang::DojangTimer,
+    exp::ExpTimer,
     jinhillah::JinhillahTimer,
-    match_agent::MatchAgent,
+    match_agent::{self, MatchAgent},
+    message::{MessageHub, MessageTimer, SystemMessage},
+    minimap::MinimapTimer,
+    player_gauge::PlayerGaugeTimer,
+    position::PositionTimer,
     vskill::{VSkillKind, VSkillTimer},
-    Timer,
+    StatusLevel, Timer,
 };
 
+/// Features whose assets and screen geometry are not yet checked against screenshots of the game.
+/// Their settings are only shown with the `experimental` feature.
+const EXPERIMENTAL: bool = cfg!(feature = "experimental");
+
 struct MatchOptions {
     jinhillah: bool,
     jinhillah_hard: bool,
     vskill: bool,
     vskill_kind: VSkillKind,
+    cooldown: bool,
+    cooldown_kind: CooldownKind,
+    player_gauge: bool,
+    hp_alert_percent: f64,
+    mp_alert_percent: f64,
+    exp: bool,
+    exp_window_minutes: u64,
+    dojang: bool,
+    minimap: bool,
+    position: bool,
+    idle_alert_secs: u64,
+    messages: bool,
+    debuff: bool,
+    debuff_alerts: [DebuffAlert; DebuffKind::ALL.len()],
+    debuff_expire_percent: f64,
 }
 
 impl Default for MatchOptions {
@@ -51,6 +85,24 @@ impl Default for MatchOptions {
             jinhillah_hard: true,
             vskill: false,
             vskill_kind: VSkillKind::FatalStrike,
+            cooldown: false,
+            cooldown_kind: CooldownKind::SpiderInMirror,
+            player_gauge: false,
+            hp_alert_percent: 30.0,
+            mp_alert_percent: 10.0,
+            exp: false,
+            exp_window_minutes: 10,
+            dojang: false,
+            minimap: false,
+            position: false,
+            idle_alert_secs: 60,
+            messages: false,
+            debuff: false,
+            debuff_alerts: [DebuffAlert {
+                appear: true,
+                expire: false,
+            }; DebuffKind::ALL.len()],
+            debuff_expire_percent: 20.0,
         }
     }
 }
@@ -191,7 +243,14 @@ impl MyEguiApp {
             ui.horizontal(|ui| {
                 ui.label(format!("{}:", timer.text()));
                 let remaining = timer.remaining_time();
-                if let Some(remaining) = remaining {
+                if let Some((status, level)) = timer.status() {
+                    let color = match level {
+                        StatusLevel::Red => Color32::from_rgb(240, 30, 30),
+                        StatusLevel::Yellow => Color32::from_rgb(240, 240, 30),
+                        StatusLevel::Normal => Color32::from_rgb(200, 200, 200),
+                    };
+                    ui.label(RichText::new(status).color(color));
+                } else if let Some(remaining) = remaining {
                     let fmt = if remaining >= Duration::from_secs(60) {
                         format!(
                             "{:02}:{:02}",
@@ -220,6 +279,7 @@ impl MyEguiApp {
                     ui.label(RichText::new(timer.debug_string()).small());
                 }
             });
+            timer.extra_ui(ui);
         }
     }
 
@@ -294,12 +354,20 @@ impl MyEguiApp {
                 .last_get
                 .map(|x| Instant::now().saturating_duration_since(x) > Duration::from_millis(200))
                 .unwrap_or(true);
+            // Annotate the preview with how each matcher sees the screen in the debug mode
+            let debug_render = self.debug && self.preview_check;
+            match_agent::DEBUG_RENDER.store(debug_render, std::sync::atomic::Ordering::SeqCst);
             if self.preview_check && elapsed {
                 trace!("Acquiring capturer");
                 let img = capturer.lock_ref().read().clone();
                 trace!("Released capturer");
                 self.last_get = Some(Instant::now());
-                if let Some(img) = img {
+                if let Some(mut img) = img {
+                    if debug_render {
+                        for (_, report) in match_agent::debug_reports() {
+                            report.annotate(&mut img);
+                        }
+                    }
                     if img.pixels().count() > 0 {
                         let new_img = RgbaImage::from_fn(img.width(), img.height(), |x, y| {
                             img.get_pixel(x, y).to_rgba()
@@ -336,6 +404,21 @@ impl MyEguiApp {
             if let Some(texture) = &self.preview_texture {
                 ui.image(texture.get().0, texture.get().1);
             }
+            if self.debug {
+                for (name, report) in match_agent::debug_reports() {
+                    let matched = report
+                        .candidates
+                        .iter()
+                        .filter(|x| x.outcome == Outcome::Matched)
+                        .count();
+                    ui.small(format!(
+                        "{}: 후보 {}, 일치 {}",
+                        name,
+                        report.candidates.len(),
+                        matched
+                    ));
+                }
+            }
         }
 
         ui.allocate_ui_with_layout(
@@ -345,7 +428,16 @@ impl MyEguiApp {
                 ui.horizontal_wrapped(|ui| {
                     ui.heading("옵션");
                     // TODO: Refactor this into method
-                    let something = self.match_options.jinhillah || self.match_options.vskill;
+                    let something = self.match_options.jinhillah
+                        || self.match_options.vskill
+                        || self.match_options.cooldown
+                        || self.match_options.player_gauge
+                        || self.match_options.exp
+                        || self.match_options.dojang
+                        || self.match_options.minimap
+                        || self.match_options.position
+                        || self.match_options.messages
+                        || self.match_options.debuff;
                     ui.add_enabled_ui(something, |ui| {
                         ui.vertical(|ui| {
                             ui.add_space(14.0);
@@ -400,6 +492,169 @@ impl MyEguiApp {
                     "일격필살",
                 );
             });
+            if EXPERIMENTAL {
+                ui.horizontal_wrapped(|ui| {
+                    let available = self.match_options.cooldown_kind.available();
+                    if !available {
+                        self.match_options.cooldown = false;
+                    }
+                    ui.add_enabled(
+                        available,
+                        egui::Checkbox::new(
+                            &mut self.match_options.cooldown,
+                            "스킬 쿨타임 타이머 사용하기",
+                        ),
+                    );
+                    for kind in CooldownKind::ALL {
+                        ui.selectable_value(
+                            &mut self.match_options.cooldown_kind,
+                            kind,
+                            kind.name(),
+                        );
+                    }
+                    if !available {
+                        warn_icon(ui, "스킬 아이콘 파일이 없어 사용할 수 없습니다.");
+                    }
+                });
+            }
+            ui.horizontal_wrapped(|ui| {
+                ui.checkbox(&mut self.match_options.dojang, "무릉도장 타이머 사용하기");
+            });
+            if EXPERIMENTAL {
+                ui.horizontal_wrapped(|ui| {
+                    ui.add_enabled(
+                        MinimapMatcher::available(),
+                        egui::Checkbox::new(
+                            &mut self.match_options.minimap,
+                            "미니맵 룬/엘리트 보스/특수 포탈 알림 사용하기",
+                        ),
+                    );
+                    if !MinimapMatcher::available() {
+                        warn_icon(ui, "미니맵 테두리 이미지 파일이 없어 사용할 수 없습니다.");
+                    } else {
+                        let missing = MinimapEntity::ALL
+                            .iter()
+                            .filter(|x| !x.available())
+                            .map(|x| match x {
+                                MinimapEntity::Rune => "룬",
+                                MinimapEntity::EliteBoss => "엘리트 보스",
+                                MinimapEntity::SpecialPortal => "특수 포탈",
+                            })
+                            .collect::<Vec<_>>();
+                        if !missing.is_empty() {
+                            warn_icon(
+                                ui,
+                                format!(
+                                    "아이콘 파일이 없어 찾을 수 없습니다: {}",
+                                    missing.join(", ")
+                                ),
+                            );
+                        }
+                    }
+                });
+            }
+            if EXPERIMENTAL {
+                ui.horizontal_wrapped(|ui| {
+                    ui.add_enabled(
+                        MinimapMatcher::available(),
+                        egui::Checkbox::new(
+                            &mut self.match_options.position,
+                            "미니맵 위치 기록 사용하기",
+                        ),
+                    );
+                    if !MinimapMatcher::available() {
+                        warn_icon(ui, "미니맵 테두리 이미지 파일이 없어 사용할 수 없습니다.");
+                    }
+                    ui.label("잠수 경고");
+                    ui.add(
+                        egui::DragValue::new(&mut self.match_options.idle_alert_secs)
+                            .clamp_range(5..=3600)
+                            .suffix("초"),
+                    );
+                });
+            }
+            if EXPERIMENTAL {
+                ui.horizontal_wrapped(|ui| {
+                    ui.add_enabled(
+                        SystemMessage::available(),
+                        egui::Checkbox::new(
+                            &mut self.match_options.messages,
+                            "시스템 메시지 표시하기",
+                        ),
+                    );
+                    if !SystemMessage::available() {
+                        warn_icon(ui, "메시지 이미지 파일이 없어 사용할 수 없습니다.");
+                    }
+                });
+            }
+            if EXPERIMENTAL {
+                ui.horizontal_wrapped(|ui| {
+                    let available = DebuffKind::ALL.iter().any(|x| x.available());
+                    ui.add_enabled(
+                        available,
+                        egui::Checkbox::new(&mut self.match_options.debuff, "디버프 알림 사용하기"),
+                    );
+                    if !available {
+                        warn_icon(ui, "디버프 아이콘 파일이 없어 사용할 수 없습니다.");
+                    }
+                    ui.label("해제 임박");
+                    ui.add(
+                        egui::DragValue::new(&mut self.match_options.debuff_expire_percent)
+                            .clamp_range(0.0..=100.0)
+                            .suffix("%"),
+                    );
+                });
+                ui.horizontal_wrapped(|ui| {
+                    for (kind, alert) in DebuffKind::ALL
+                        .iter()
+                        .zip(self.match_options.debuff_alerts.iter_mut())
+                    {
+                        ui.add_enabled_ui(kind.available(), |ui| {
+                            ui.label(kind.name());
+                            ui.checkbox(&mut alert.appear, "등장");
+                            ui.checkbox(&mut alert.expire, "해제 임박");
+                        });
+                    }
+                });
+            }
+            if EXPERIMENTAL {
+                ui.horizontal_wrapped(|ui| {
+                    ui.add_enabled(
+                        PlayerGaugeMatcher::available(),
+                        egui::Checkbox::new(
+                            &mut self.match_options.player_gauge,
+                            "HP/MP 경고 사용하기",
+                        ),
+                    );
+                    if !PlayerGaugeMatcher::available() {
+                        warn_icon(ui, "상태 바 이미지 파일이 없어 사용할 수 없습니다.");
+                    }
+                    ui.label("HP");
+                    ui.add(
+                        egui::DragValue::new(&mut self.match_options.hp_alert_percent)
+                            .clamp_range(0.0..=100.0)
+                            .suffix("%"),
+                    );
+                    ui.label("MP");
+                    ui.add(
+                        egui::DragValue::new(&mut self.match_options.mp_alert_percent)
+                            .clamp_range(0.0..=100.0)
+                            .suffix("%"),
+                    );
+                });
+            }
+            if EXPERIMENTAL {
+                ui.horizontal_wrapped(|ui| {
+                    ui.checkbox(&mut self.match_options.exp, "경험치 효율 측정하기");
+                    for minutes in [5, 10, 30, 60] {
+                        ui.selectable_value(
+                            &mut self.match_options.exp_window_minutes,
+                            minutes,
+                            format!("{}분", minutes),
+                        );
+                    }
+                });
+            }
         });
     }
 
@@ -421,6 +676,71 @@ impl MyEguiApp {
                 self.capturer.as_mut().unwrap().as_mut().unwrap().dims(),
             )))
         }
+
+        if self.match_options.cooldown {
+            self.timers.push(Box::new(CooldownTimer::new(
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().cond()),
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().lock_ref()),
+                self.match_options.cooldown_kind,
+            )));
+        }
+
+        if self.match_options.dojang {
+            self.timers.push(Box::new(DojangTimer::new(
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().cond()),
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().lock_ref()),
+            )));
+        }
+
+        if self.match_options.player_gauge {
+            self.timers.push(Box::new(PlayerGaugeTimer::new(
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().cond()),
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().lock_ref()),
+                self.match_options.hp_alert_percent / 100.0,
+                self.match_options.mp_alert_percent / 100.0,
+            )));
+        }
+
+        if self.match_options.minimap {
+            self.timers.push(Box::new(MinimapTimer::new(
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().cond()),
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().lock_ref()),
+            )));
+        }
+
+        if self.match_options.position {
+            self.timers.push(Box::new(PositionTimer::new(
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().cond()),
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().lock_ref()),
+                Duration::from_secs(self.match_options.idle_alert_secs),
+            )));
+        }
+
+        if self.match_options.messages {
+            let hub = MessageHub::new(
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().cond()),
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().lock_ref()),
+            );
+            self.timers.push(Box::new(MessageTimer::new(&hub)));
+        }
+
+        if self.match_options.debuff {
+            self.timers.push(Box::new(DebuffTimer::new(
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().cond()),
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().lock_ref()),
+                self.capturer.as_mut().unwrap().as_mut().unwrap().dims(),
+                self.match_options.debuff_alerts,
+                self.match_options.debuff_expire_percent / 100.0,
+            )));
+        }
+
+        if self.match_options.exp {
+            self.timers.push(Box::new(ExpTimer::new(
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().cond()),
+                Arc::clone(self.capturer.as_mut().unwrap().as_mut().unwrap().lock_ref()),
+                Duration::from_secs(self.match_options.exp_window_minutes * 60),
+            )));
+        }
     }
 }
 
@@ -481,9 +801,13 @@ fn main() {
         hook(e);
     }));
 
-    <JinHillahHpMatcher as Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>>::init();
     <JinHillahReapMatcher as Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>>::init();
     <BuffMatcher as Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>>::init();
+    <PlayerGaugeMatcher as Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>>::init();
+    <QuickslotMatcher as Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>>::init();
+    <MinimapMatcher as Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>>::init();
+    <DojangClearMatcher as Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>>::init();
+    <ExpMatcher as Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>>::init();
 
     let app = MyEguiApp::default();
     assets_embedded::assets();
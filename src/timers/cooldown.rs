@@ -0,0 +1,134 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use assets_manager::asset::Png;
+use image::{Bgra, ImageBuffer};
+use image_match::{quickslot::QuickslotMatcher, BoundsCachedMatcher, InvalidationPolicy};
+use parking_lot::RwLock;
+
+use crate::rw_condvar::RwCondvar;
+
+use super::{match_agent::MatchAgent, Timer};
+
+/// Burst skills whose cooldowns are tracked on the quickslot.
+///
+/// Icons are read from `assets/quickslot/`, since the skills on the quickslot vary by class.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum CooldownKind {
+    SpiderInMirror,
+    CrestOfTheSolar,
+    SeedRing,
+}
+
+impl CooldownKind {
+    pub const ALL: [CooldownKind; 3] = [
+        CooldownKind::SpiderInMirror,
+        CooldownKind::CrestOfTheSolar,
+        CooldownKind::SeedRing,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CooldownKind::SpiderInMirror => "스파이더 인 미러",
+            CooldownKind::CrestOfTheSolar => "크레스트 오브 더 솔라",
+            CooldownKind::SeedRing => "시드링",
+        }
+    }
+
+    fn asset(self) -> &'static str {
+        match self {
+            CooldownKind::SpiderInMirror => "quickslot.spider_in_mirror",
+            CooldownKind::CrestOfTheSolar => "quickslot.crest_of_the_solar",
+            CooldownKind::SeedRing => "quickslot.seed_ring",
+        }
+    }
+
+    /// Cooldown without cooldown reduction.
+    pub fn cooldown(self) -> Duration {
+        match self {
+            CooldownKind::SpiderInMirror => Duration::from_secs(250),
+            CooldownKind::CrestOfTheSolar => Duration::from_secs(250),
+            CooldownKind::SeedRing => Duration::from_secs(180),
+        }
+    }
+
+    fn icon(self) -> Option<ImageBuffer<Bgra<u8>, Vec<u8>>> {
+        Some(
+            assets_embedded::assets()
+                .load::<Png>(self.asset())
+                .ok()?
+                .cloned()
+                .0
+                .to_bgra8(),
+        )
+    }
+
+    /// Whether the quickslot icon of the skill is available.
+    pub fn available(self) -> bool {
+        self.icon().is_some()
+    }
+}
+
+/// Shows the remaining cooldown of a skill on the quickslot.
+pub struct CooldownTimer {
+    matcher: MatchAgent<BoundsCachedMatcher<QuickslotMatcher>>,
+    kind: CooldownKind,
+}
+
+impl CooldownTimer {
+    /// Panics if the icon of the skill is not available.
+    pub fn new(
+        cond: Arc<RwCondvar>,
+        image_lock: Arc<RwLock<Option<ImageBuffer<Bgra<u8>, Vec<u8>>>>>,
+        kind: CooldownKind,
+    ) -> Self {
+        Self {
+            matcher: MatchAgent::new(
+                BoundsCachedMatcher::with_policy(
+                    QuickslotMatcher::new(kind.icon().unwrap(), 0.8),
+                    InvalidationPolicy {
+                        max_misses: Some(20),
+                        ..Default::default()
+                    },
+                ),
+                cond,
+                image_lock,
+                Some(Duration::from_millis(250)),
+                false,
+            ),
+            kind,
+        }
+    }
+}
+
+impl Timer for CooldownTimer {
+    fn duration(&mut self) -> Duration {
+        self.kind.cooldown()
+    }
+
+    fn last_match(&mut self) -> Option<Instant> {
+        // When the skill was used, estimated from the remaining cooldown
+        let result = self.matcher.read_result()?;
+        let recv = self.matcher.last_recv()?;
+        let elapsed = self
+            .duration()
+            .saturating_sub(result.remaining(self.duration()));
+        Some(recv.checked_sub(elapsed).unwrap_or(recv))
+    }
+
+    fn text(&self) -> &str {
+        self.kind.name()
+    }
+
+    fn is_panicked(&self) -> bool {
+        self.matcher.is_panicked()
+    }
+
+    fn debug_string(&mut self) -> String {
+        format!("{:?}", self.matcher.read_result())
+    }
+
+    fn wake(&mut self) {}
+}
@@ -0,0 +1,192 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use assets_manager::asset::Png;
+use image::{Bgra, ImageBuffer};
+use image_match::{buff::DEFAULT_THRESHOLD, debuff::DebuffScanner};
+use once_cell::sync::OnceCell;
+use parking_lot::RwLock;
+
+use crate::rw_condvar::RwCondvar;
+
+use super::{match_agent::MatchAgent, StatusLevel, Timer};
+
+/// How long a newly appeared debuff is alerted.
+const APPEAR_ALERT: Duration = Duration::from_secs(3);
+/// Icons verified for each cell of the debuff area, nearest first by perceptual hash. Debuff icons
+/// have no frame to skip empty cells by, so every cell is compared.
+const DEBUFF_CANDIDATES: usize = 2;
+
+/// Icons of [`DebuffKind::ALL`], loaded once since availability is checked on every frame.
+static DEBUFF_ICONS: OnceCell<Vec<Option<ImageBuffer<Bgra<u8>, Vec<u8>>>>> = OnceCell::new();
+
+/// Debuffs inflicted by bosses.
+///
+/// Icons are read from `assets/debuffs/`; debuffs without one are never found.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DebuffKind {
+    Stun,
+    Seal,
+    Seduce,
+    Curse,
+}
+
+impl DebuffKind {
+    pub const ALL: [DebuffKind; 4] = [
+        DebuffKind::Stun,
+        DebuffKind::Seal,
+        DebuffKind::Seduce,
+        DebuffKind::Curse,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebuffKind::Stun => "기절",
+            DebuffKind::Seal => "봉인",
+            DebuffKind::Seduce => "유혹",
+            DebuffKind::Curse => "저주",
+        }
+    }
+
+    fn asset(self) -> &'static str {
+        match self {
+            DebuffKind::Stun => "debuffs.stun",
+            DebuffKind::Seal => "debuffs.seal",
+            DebuffKind::Seduce => "debuffs.seduce",
+            DebuffKind::Curse => "debuffs.curse",
+        }
+    }
+
+    fn icon(self) -> Option<&'static ImageBuffer<Bgra<u8>, Vec<u8>>> {
+        DEBUFF_ICONS.get_or_init(|| {
+            Self::ALL
+                .iter()
+                .map(|x| {
+                    Some(
+                        assets_embedded::assets()
+                            .load::<Png>(x.asset())
+                            .ok()?
+                            .cloned()
+                            .0
+                            .to_bgra8(),
+                    )
+                })
+                .collect()
+        })[self as usize]
+            .as_ref()
+    }
+
+    /// Whether the icon of the debuff is available.
+    pub fn available(self) -> bool {
+        self.icon().is_some()
+    }
+}
+
+/// When to alert about a debuff.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DebuffAlert {
+    pub appear: bool,
+    pub expire: bool,
+}
+
+/// Shows the debuffs on the player, and alerts when they appear or are about to expire.
+pub struct DebuffTimer {
+    matcher: MatchAgent<DebuffScanner<DebuffKind>>,
+    /// Indexed by [`DebuffKind`].
+    alerts: [DebuffAlert; DebuffKind::ALL.len()],
+    /// Remaining ratio of the duration below which a debuff is about to expire.
+    expire_ratio: f64,
+    /// When each active debuff was first seen.
+    appeared: Vec<(DebuffKind, Instant)>,
+}
+
+impl DebuffTimer {
+    pub fn new(
+        cond: Arc<RwCondvar>,
+        image_lock: Arc<RwLock<Option<ImageBuffer<Bgra<u8>, Vec<u8>>>>>,
+        dims: (u32, u32),
+        alerts: [DebuffAlert; DebuffKind::ALL.len()],
+        expire_ratio: f64,
+    ) -> Self {
+        let catalog = DebuffKind::ALL
+            .iter()
+            .filter_map(|&kind| Some((kind, kind.icon()?.clone())));
+        Self {
+            matcher: MatchAgent::new(
+                DebuffScanner::new(catalog, DEFAULT_THRESHOLD, dims).with_index(DEBUFF_CANDIDATES),
+                cond,
+                image_lock,
+                Some(Duration::from_millis(250)),
+                false,
+            ),
+            alerts,
+            expire_ratio,
+            appeared: Vec::new(),
+        }
+    }
+
+    fn update(&mut self) {
+        if let Some(active) = self.matcher.read_new_result() {
+            let now = self.matcher.last_recv().unwrap_or_else(Instant::now);
+            self.appeared
+                .retain(|(kind, _)| active.iter().any(|x| x.kind == *kind));
+            for x in &active {
+                if !self.appeared.iter().any(|(kind, _)| *kind == x.kind) {
+                    self.appeared.push((x.kind, now));
+                }
+            }
+        }
+    }
+}
+
+impl Timer for DebuffTimer {
+    fn duration(&mut self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn last_match(&mut self) -> Option<Instant> {
+        self.matcher.last_recv()
+    }
+
+    fn remaining_time(&mut self) -> Option<Duration> {
+        None
+    }
+
+    fn text(&self) -> &str {
+        "디버프"
+    }
+
+    fn is_panicked(&self) -> bool {
+        self.matcher.is_panicked()
+    }
+
+    fn status(&mut self) -> Option<(String, StatusLevel)> {
+        self.update();
+        let active = self.matcher.read_result()?;
+        if active.is_empty() {
+            return Some((String::from("없음"), StatusLevel::Normal));
+        }
+
+        let mut level = StatusLevel::Normal;
+        let mut texts = Vec::with_capacity(active.len());
+        for x in &active {
+            let alert = self.alerts[x.kind as usize];
+            let ratio = x.result.remaining_ratio();
+            let new = self
+                .appeared
+                .iter()
+                .any(|(kind, at)| *kind == x.kind && at.elapsed() < APPEAR_ALERT);
+            if alert.appear && new {
+                level = StatusLevel::Red;
+            } else if alert.expire && ratio <= self.expire_ratio && level == StatusLevel::Normal {
+                level = StatusLevel::Yellow;
+            }
+            texts.push(format!("{} {:.0}%", x.kind.name(), ratio * 100.0));
+        }
+        Some((texts.join(", "), level))
+    }
+
+    fn wake(&mut self) {}
+}
@@ -0,0 +1,235 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use image::{Bgra, ImageBuffer};
+use image_match::dojang::{DojangClearMatcher, ScreenFadeMatcher};
+use parking_lot::RwLock;
+
+use crate::rw_condvar::RwCondvar;
+
+use super::{match_agent::MatchAgent, StatusLevel, Timer};
+
+/// The clear banner stays on the screen for a few seconds. Matches closer than this to the last
+/// one belong to the same clear.
+const BANNER_DURATION: Duration = Duration::from_secs(5);
+
+/// Splits of a Mu Lung Dojang run. Each floor is timed from the end of the fade into it to its
+/// clear banner.
+pub struct DojangRun {
+    start: Instant,
+    floor_entry: Instant,
+    /// Time taken by each cleared floor.
+    splits: Vec<Duration>,
+    /// Whether the current floor is cleared.
+    cleared: bool,
+    last_clear: Option<Instant>,
+    last_banner: Option<Instant>,
+    fading: bool,
+}
+
+impl DojangRun {
+    pub fn new(start: Instant) -> Self {
+        Self {
+            start,
+            floor_entry: start,
+            splits: Vec::new(),
+            cleared: false,
+            last_clear: None,
+            last_banner: None,
+            fading: false,
+        }
+    }
+
+    pub fn push_banner(&mut self, at: Instant) {
+        let new_banner = !matches!(
+            self.last_banner,
+            Some(last) if at.saturating_duration_since(last) < BANNER_DURATION
+        );
+        self.last_banner = Some(at);
+        if new_banner && !self.cleared {
+            self.splits
+                .push(at.saturating_duration_since(self.floor_entry));
+            self.cleared = true;
+            self.last_clear = Some(at);
+        }
+    }
+
+    /// Whether the screen is black at `at`. A floor is entered when the screen comes back from
+    /// black.
+    pub fn push_fade(&mut self, at: Instant, black: bool) {
+        if black {
+            self.fading = true;
+        } else if self.fading {
+            self.fading = false;
+            self.floor_entry = at;
+            self.cleared = false;
+        }
+    }
+
+    /// Current floor, counting from the floor where the timers were started.
+    pub fn floor(&self) -> usize {
+        if self.cleared {
+            self.splits.len()
+        } else {
+            self.splits.len() + 1
+        }
+    }
+
+    /// Time spent on the current floor, which stops on its clear.
+    pub fn floor_time(&self, now: Instant) -> Duration {
+        match (self.cleared, self.splits.last()) {
+            (true, Some(split)) => *split,
+            _ => now.saturating_duration_since(self.floor_entry),
+        }
+    }
+
+    pub fn total_time(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.start)
+    }
+
+    pub fn splits(&self) -> &[Duration] {
+        &self.splits
+    }
+
+    pub fn last_clear(&self) -> Option<Instant> {
+        self.last_clear
+    }
+}
+
+/// Measures a Mu Lung Dojang run from the start of the timers, splitting on each floor clear.
+pub struct DojangTimer {
+    clear_matcher: MatchAgent<DojangClearMatcher>,
+    fade_matcher: MatchAgent<ScreenFadeMatcher>,
+    run: DojangRun,
+}
+
+impl DojangTimer {
+    pub fn new(
+        cond: Arc<RwCondvar>,
+        image_lock: Arc<RwLock<Option<ImageBuffer<Bgra<u8>, Vec<u8>>>>>,
+    ) -> Self {
+        Self {
+            clear_matcher: MatchAgent::new(
+                DojangClearMatcher,
+                Arc::clone(&cond),
+                Arc::clone(&image_lock),
+                Some(Duration::from_millis(200)),
+                false,
+            ),
+            fade_matcher: MatchAgent::new(
+                ScreenFadeMatcher,
+                cond,
+                image_lock,
+                Some(Duration::from_millis(100)),
+                false,
+            ),
+            run: DojangRun::new(Instant::now()),
+        }
+    }
+
+    fn update(&mut self) {
+        while self.clear_matcher.read_new_result().is_some() {
+            self.run
+                .push_banner(self.clear_matcher.last_recv().unwrap());
+        }
+        while let Some(result) = self.fade_matcher.read_new_result() {
+            self.run
+                .push_fade(self.fade_matcher.last_recv().unwrap(), result.black());
+        }
+    }
+}
+
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    format!(
+        "{:02}:{:02}.{}",
+        secs / 60,
+        secs % 60,
+        d.subsec_millis() / 100
+    )
+}
+
+impl Timer for DojangTimer {
+    fn duration(&mut self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn last_match(&mut self) -> Option<Instant> {
+        self.update();
+        self.run.last_clear()
+    }
+
+    fn remaining_time(&mut self) -> Option<Duration> {
+        None
+    }
+
+    fn text(&self) -> &str {
+        "무릉도장"
+    }
+
+    fn is_panicked(&self) -> bool {
+        self.clear_matcher.is_panicked() || self.fade_matcher.is_panicked()
+    }
+
+    fn status(&mut self) -> Option<(String, StatusLevel)> {
+        self.update();
+        let now = Instant::now();
+        let mut status = format!(
+            "{}번째 층 {} (총 {})",
+            self.run.floor(),
+            format_duration(self.run.floor_time(now)),
+            format_duration(self.run.total_time(now)),
+        );
+        if let Some(last) = self.run.splits().last() {
+            status += &format!(", 직전 층 {}", format_duration(*last));
+        }
+        Some((status, StatusLevel::Normal))
+    }
+
+    fn debug_string(&mut self) -> String {
+        self.update();
+        self.run
+            .splits()
+            .iter()
+            .enumerate()
+            .map(|(i, split)| format!("{}: {}", i + 1, format_duration(*split)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn wake(&mut self) {}
+}
+
+#[test]
+fn dojang_splits_from_floor_entry() {
+    let start = Instant::now();
+    let at = |secs| start + Duration::from_secs(secs);
+    let mut run = DojangRun::new(start);
+
+    // The first floor is timed from the start, as its entry is not seen
+    run.push_fade(at(1), false);
+    run.push_banner(at(30));
+    run.push_banner(at(32));
+    assert_eq!(run.splits(), [Duration::from_secs(30)]);
+    assert_eq!(run.floor(), 1);
+    assert_eq!(run.floor_time(at(40)), Duration::from_secs(30));
+
+    // The banner is seen again after the clear, before leaving the floor
+    run.push_banner(at(38));
+    assert_eq!(run.splits().len(), 1);
+
+    // Moving to the next floor
+    run.push_fade(at(45), true);
+    run.push_fade(at(46), true);
+    run.push_fade(at(47), false);
+    assert_eq!(run.floor(), 2);
+    assert_eq!(run.floor_time(at(50)), Duration::from_secs(3));
+    run.push_banner(at(67));
+    assert_eq!(
+        run.splits(),
+        [Duration::from_secs(30), Duration::from_secs(20)]
+    );
+    assert_eq!(run.total_time(at(67)), Duration::from_secs(67));
+}
@@ -0,0 +1,201 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use image::{Bgra, ImageBuffer};
+use image_match::{
+    exp::{ExpMatchResult, ExpMatcher},
+    filter::{Filter, Median},
+};
+use parking_lot::RwLock;
+
+use crate::rw_condvar::RwCondvar;
+
+use super::{match_agent::MatchAgent, StatusLevel, Timer};
+
+/// EXP gained per hour over a rolling window.
+pub struct ExpRate {
+    window: Duration,
+    /// `(instant, cumulative percent)` pairs. Level ups add 100 to the cumulative percent.
+    samples: VecDeque<(Instant, f64)>,
+    level_ups: u32,
+    last_percent: Option<f64>,
+}
+
+impl ExpRate {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+            level_ups: 0,
+            last_percent: None,
+        }
+    }
+
+    pub fn push(&mut self, at: Instant, percent: f64) {
+        // EXP only goes back to near zero on level up; small decreases are misreads or deaths.
+        if matches!(self.last_percent, Some(last) if last - percent > 50.0) {
+            self.level_ups += 1;
+        }
+        self.last_percent = Some(percent);
+        self.samples
+            .push_back((at, self.level_ups as f64 * 100.0 + percent));
+
+        while let Some(&(first, _)) = self.samples.front() {
+            if at.saturating_duration_since(first) <= self.window {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// EXP percent per hour.
+    pub fn per_hour(&self) -> Option<f64> {
+        let (first, last) = (self.samples.front()?, self.samples.back()?);
+        let elapsed = last.0.saturating_duration_since(first.0).as_secs_f64();
+        if elapsed < 10.0 {
+            return None;
+        }
+        Some((last.1 - first.1) / elapsed * 3600.0)
+    }
+
+    pub fn time_to_level(&self) -> Option<Duration> {
+        let per_hour = self.per_hour().filter(|&x| x > 0.0)?;
+        let remaining = 100.0 - self.last_percent?;
+        Some(Duration::from_secs_f64(remaining / per_hour * 3600.0))
+    }
+
+    pub fn level_ups(&self) -> u32 {
+        self.level_ups
+    }
+}
+
+/// Tracks EXP gain rate while the timers are running.
+pub struct ExpTimer {
+    matcher: MatchAgent<ExpMatcher>,
+    filter: Median,
+    rate: ExpRate,
+    start: Instant,
+    raw: Option<ExpMatchResult>,
+}
+
+impl ExpTimer {
+    pub fn new(
+        cond: Arc<RwCondvar>,
+        image_lock: Arc<RwLock<Option<ImageBuffer<Bgra<u8>, Vec<u8>>>>>,
+        window: Duration,
+    ) -> Self {
+        Self {
+            matcher: MatchAgent::new(
+                ExpMatcher,
+                cond,
+                image_lock,
+                Some(Duration::from_secs(1)),
+                false,
+            ),
+            filter: Median::new(5),
+            rate: ExpRate::new(window),
+            start: Instant::now(),
+            raw: None,
+        }
+    }
+
+    fn update(&mut self) {
+        if let Some(result) = self.matcher.read_new_result() {
+            let percent = self.filter.push(result.percent());
+            self.rate.push(self.matcher.last_recv().unwrap(), percent);
+            self.raw = Some(result);
+        }
+    }
+}
+
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+impl Timer for ExpTimer {
+    fn duration(&mut self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn last_match(&mut self) -> Option<Instant> {
+        self.update();
+        self.matcher.last_recv()
+    }
+
+    fn remaining_time(&mut self) -> Option<Duration> {
+        self.update();
+        self.rate.time_to_level()
+    }
+
+    fn text(&self) -> &str {
+        "경험치"
+    }
+
+    fn is_panicked(&self) -> bool {
+        self.matcher.is_panicked()
+    }
+
+    fn status(&mut self) -> Option<(String, StatusLevel)> {
+        self.update();
+        let per_hour = match self.rate.per_hour() {
+            Some(per_hour) => per_hour,
+            None => return Some((String::from("측정 중..."), StatusLevel::Normal)),
+        };
+        let time_to_level = self
+            .rate
+            .time_to_level()
+            .map(format_duration)
+            .unwrap_or_else(|| String::from("―"));
+        Some((
+            format!("{:.2}%/h, 레벨업까지 {}", per_hour, time_to_level),
+            StatusLevel::Normal,
+        ))
+    }
+
+    fn debug_string(&mut self) -> String {
+        self.update();
+        format!(
+            "raw: {:?}, levelUps: {}, elapsed: {}",
+            self.raw,
+            self.rate.level_ups(),
+            format_duration(Instant::now().saturating_duration_since(self.start)),
+        )
+    }
+
+    fn wake(&mut self) {}
+}
+
+#[test]
+fn exp_rate_window() {
+    let start = Instant::now();
+    let mut rate = ExpRate::new(Duration::from_secs(60));
+    rate.push(start, 10.0);
+    rate.push(start + Duration::from_secs(5), 10.5);
+    assert!(rate.per_hour().is_none());
+
+    // 1% per minute, then 2% per minute
+    rate.push(start + Duration::from_secs(60), 11.0);
+    assert!((rate.per_hour().unwrap() - 60.0).abs() < 1e-6);
+    rate.push(start + Duration::from_secs(120), 13.0);
+    assert!((rate.per_hour().unwrap() - 120.0).abs() < 1e-6);
+    assert_eq!(rate.time_to_level(), Some(Duration::from_secs(87 * 30)));
+}
+
+#[test]
+fn exp_rate_level_up() {
+    let start = Instant::now();
+    let mut rate = ExpRate::new(Duration::from_secs(600));
+    rate.push(start, 90.0);
+    // Deaths and misreads lower the percent a little
+    rate.push(start + Duration::from_secs(60), 80.0);
+    assert_eq!(rate.level_ups(), 0);
+    rate.push(start + Duration::from_secs(120), 95.0);
+    rate.push(start + Duration::from_secs(180), 5.0);
+    assert_eq!(rate.level_ups(), 1);
+    // 15% in 3 minutes
+    assert!((rate.per_hour().unwrap() - 300.0).abs() < 1e-6);
+}
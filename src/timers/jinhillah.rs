@@ -5,22 +5,33 @@ use std::{
 
 use image::{Bgra, ImageBuffer};
 use image_match::{
-    jinhillah::{JinHillahHpMatcher, JinHillahReapMatcher},
-    BoundsCachedMatcher,
+    boss_hp::{
+        BossEvent, BossFightTracker, BossHpBarMatchResult, BossHpBarMatcher, CalibrationReset,
+        JIN_HILLAH,
+    },
+    filter::{Filter, Median, Monotonic, OutlierRejection},
+    jinhillah::JinHillahReapMatcher,
+    BoundsCachedMatcher, InvalidationPolicy,
 };
-use log::trace;
+use log::{info, trace};
 use parking_lot::RwLock;
 
 use crate::{rw_condvar::RwCondvar, MatchAgent};
 
-use super::Timer;
+use super::{StatusLevel, Timer};
 
 pub struct JinhillahTimer {
-    hp: MatchAgent<BoundsCachedMatcher<JinHillahHpMatcher>>,
+    hp: MatchAgent<BoundsCachedMatcher<BossHpBarMatcher>>,
+    hp_calibration: CalibrationReset,
     reap: MatchAgent<BoundsCachedMatcher<JinHillahReapMatcher>>,
     normal_mode: bool,
     capture_time: Option<Instant>,
     duration_at_capture: Duration,
+    hp_filter: (OutlierRejection, Median, Monotonic),
+    raw_hp: Option<BossHpBarMatchResult>,
+    filtered_hp_ratio: Option<f64>,
+    fight: BossFightTracker,
+    last_event: Option<(BossEvent, Instant)>,
 }
 
 impl JinhillahTimer {
@@ -30,16 +41,26 @@ impl JinhillahTimer {
         dimensions: (u32, u32),
         normal_mode: bool,
     ) -> Self {
+        let hp_matcher = BossHpBarMatcher::new(JIN_HILLAH).unwrap();
+        let hp_calibration = hp_matcher.calibration_reset();
         Self {
             hp: MatchAgent::new(
-                BoundsCachedMatcher::new(JinHillahHpMatcher),
+                BoundsCachedMatcher::with_policy(
+                    hp_matcher,
+                    InvalidationPolicy {
+                        max_misses: Some(40),
+                        rescan_interval: Some(Duration::from_secs(10)),
+                        ..Default::default()
+                    },
+                ),
                 Arc::clone(&cond),
                 Arc::clone(&image_lock),
                 None,
                 false,
             ),
+            hp_calibration,
             reap: MatchAgent::new(
-                BoundsCachedMatcher::new(JinHillahReapMatcher(dimensions.0, dimensions.1)),
+                BoundsCachedMatcher::new(JinHillahReapMatcher::new(dimensions)),
                 cond,
                 image_lock,
                 Some(Duration::from_millis(490)),
@@ -52,8 +73,20 @@ impl JinhillahTimer {
             } else {
                 Duration::from_secs(150)
             },
+            hp_filter: (
+                OutlierRejection::new(0.1, 3),
+                Median::new(5),
+                Monotonic::decreasing(),
+            ),
+            raw_hp: None,
+            filtered_hp_ratio: None,
+            fight: BossFightTracker::default(),
+            last_event: None,
         }
     }
+
+    /// The end of a fight is shown for this long.
+    const EVENT_DURATION: Duration = Duration::from_secs(10);
 }
 
 impl JinhillahTimer {
@@ -86,11 +119,42 @@ impl JinhillahTimer {
         }
     }
 
+    /// Feeds a newly arrived HP match result, if any, into the filter, and resets the timer when
+    /// the fight is over.
+    fn update_hp(&mut self) {
+        if let Some(result) = self.hp.read_new_result() {
+            let ratio = result.total_hp_ratio();
+            self.filtered_hp_ratio = Some(self.hp_filter.push(ratio));
+            self.fight.push(self.hp.last_recv().unwrap(), &result);
+            self.raw_hp = Some(result);
+        }
+        if let Some(event) = self.fight.poll(Instant::now()) {
+            info!("Jin Hillah fight is over: {:?}", event);
+            self.last_event = Some((event, Instant::now()));
+            self.reset();
+        }
+    }
+
+    /// Forgets the current fight.
+    fn reset(&mut self) {
+        self.capture_time = None;
+        self.duration_at_capture = if self.normal_mode {
+            Duration::from_secs(180)
+        } else {
+            Duration::from_secs(150)
+        };
+        self.hp_filter.reset();
+        self.raw_hp = None;
+        self.filtered_hp_ratio = None;
+        // Otherwise the cached reap of the last fight would start the timer again
+        self.reap.clear();
+        self.hp.clear();
+        self.hp_calibration.reset();
+    }
+
     fn total_hp_ratio(&mut self) -> f64 {
-        self.hp
-            .read_result()
-            .map(|x| (4 - x.phase()) as f64 * 0.25 + x.hp_ratio() * 0.25)
-            .unwrap_or(1.0)
+        self.update_hp();
+        self.filtered_hp_ratio.unwrap_or(1.0)
     }
 }
 
@@ -100,6 +164,7 @@ impl Timer for JinhillahTimer {
     }
 
     fn last_match(&mut self) -> Option<Instant> {
+        self.update_hp();
         let ret = self.reap.read_result().and_then(|_| self.reap.last_recv());
         match (ret, &self.capture_time) {
             (Some(x), None) => {
@@ -136,28 +201,48 @@ impl Timer for JinhillahTimer {
         self.hp.is_panicked() || self.reap.is_panicked()
     }
 
+    fn status(&mut self) -> Option<(String, StatusLevel)> {
+        self.update_hp();
+        if self.fight.in_fight() {
+            return None;
+        }
+        match self.last_event {
+            Some((event, at)) if at.elapsed() < Self::EVENT_DURATION => Some(match event {
+                BossEvent::Defeated => (String::from("처치 완료"), StatusLevel::Normal),
+                BossEvent::Left => (String::from("전멸 또는 퇴장"), StatusLevel::Yellow),
+            }),
+            _ => None,
+        }
+    }
+
     fn wake(&mut self) {
         trace!("JinhillahTimer reap wakeup");
         self.reap.wake();
     }
 
     fn debug_string(&mut self) -> String {
-        let result = self.hp.read_result();
+        self.update_hp();
+        let result = self.raw_hp.as_ref();
         let raw = result
             .as_ref()
             .map(|x| format!("{:?}", x))
             .unwrap_or_else(|| String::from("?"));
-        let phase = result
+        let layer = result
             .as_ref()
-            .map(|x| x.phase().to_string())
+            .map(|x| x.layer().to_string())
             .unwrap_or_else(|| String::from("?"));
         let ratio = result
             .map(|x| format!("{:.3}", x.hp_ratio()))
             .unwrap_or_else(|| String::from("?"));
+        let raw_total_ratio = result
+            .map(|x| format!("{:.4}", x.total_hp_ratio()))
+            .unwrap_or_else(|| String::from("?"));
         format!(
-            "dur: {:.2}, raw: {raw}, phase: {phase} ratio: {ratio}, totalRatio: {:.4}",
+            "dur: {:.2}, raw: {raw}, layer: {layer} ratio: {ratio}, \
+            rawTotalRatio: {raw_total_ratio}, totalRatio: {:.4}, lastEvent: {:?}",
             self.duration().as_secs_f64(),
             self.total_hp_ratio(),
+            self.last_event.map(|(event, _)| event),
         )
     }
 }
@@ -1,18 +1,40 @@
 use std::{
+    any::type_name,
     panic::{catch_unwind, AssertUnwindSafe},
-    sync::{atomic::AtomicBool, Arc},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize},
+        Arc,
+    },
     thread,
     time::{Duration, Instant},
 };
 
 use crossbeam_channel::{Receiver, Sender};
 use image::{Bgra, ImageBuffer};
-use image_match::Matcher;
+use image_match::{debug::DebugReport, Matcher};
 use log::trace;
-use parking_lot::RwLock;
+use parking_lot::{const_mutex, Mutex, RwLock};
 
 use crate::rw_condvar::RwCondvar;
 
+/// Whether the agents record a [`DebugReport`] of each screen, instead of stopping at the first
+/// match. Set by the debug mode of the GUI.
+pub static DEBUG_RENDER: AtomicBool = AtomicBool::new(false);
+
+/// Last report of each agent, with the agent id and the matcher type name.
+static DEBUG_REPORTS: Mutex<Vec<(usize, &'static str, DebugReport)>> = const_mutex(Vec::new());
+
+static NEXT_AGENT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Last reports of the running agents, recorded while [`DEBUG_RENDER`] is set.
+pub fn debug_reports() -> Vec<(&'static str, DebugReport)> {
+    DEBUG_REPORTS
+        .lock()
+        .iter()
+        .map(|(_, name, report)| (*name, report.clone()))
+        .collect()
+}
+
 pub struct MatchAgent<T: Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>> {
     last_result: Option<<T as Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>>::MatchResult>,
     last_recv: Option<Instant>,
@@ -23,12 +45,13 @@ pub struct MatchAgent<T: Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>> {
     panicked: Arc<AtomicBool>,
     kill: Arc<AtomicBool>,
     suspend: Arc<AtomicBool>,
+    id: usize,
 }
 
 impl<T> MatchAgent<T>
 where
     <T as Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>>::MatchResult: Send + Clone + 'static,
-    T: Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>> + Send + 'static,
+    T: Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>> + Clone + Send + 'static,
 {
     pub fn new(
         matcher: T,
@@ -42,6 +65,7 @@ where
         let panicked = Arc::new(AtomicBool::new(false));
         let kill = Arc::new(AtomicBool::new(false));
         let suspend = Arc::new(AtomicBool::new(false));
+        let id = NEXT_AGENT_ID.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
         thread::spawn({
             let cond = Arc::clone(&cond);
             let input_image = Arc::clone(&input_image);
@@ -50,6 +74,7 @@ where
             let suspend = Arc::clone(&suspend);
             move || {
                 Self::worker_entrypoint(
+                    id,
                     matcher,
                     cond,
                     input_image,
@@ -70,22 +95,34 @@ where
             panicked,
             kill,
             suspend,
+            id,
         }
     }
 
     pub fn read_result(
         &mut self,
     ) -> Option<<T as Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>>::MatchResult> {
-        self.recv
-            .try_recv()
-            .ok()
-            .map(|(x, instant)| {
-                trace!("Received");
-                self.last_result = Some(x.clone());
-                self.last_recv = Some(instant);
-                x
-            })
-            .or_else(|| self.last_result.clone())
+        self.read_new_result().or_else(|| self.last_result.clone())
+    }
+
+    /// Similar to [`MatchAgent::read_result`], but returns `None` if no result has arrived since
+    /// the last call. Useful for feeding each result into filters exactly once.
+    pub fn read_new_result(
+        &mut self,
+    ) -> Option<<T as Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>>>::MatchResult> {
+        self.recv.try_recv().ok().map(|(x, instant)| {
+            trace!("Received");
+            self.last_result = Some(x.clone());
+            self.last_recv = Some(instant);
+            x
+        })
+    }
+
+    /// Forgets the last result, including one which has arrived but not been read yet.
+    pub fn clear(&mut self) {
+        while self.recv.try_recv().is_ok() {}
+        self.last_result = None;
+        self.last_recv = None;
     }
 
     pub fn is_panicked(&self) -> bool {
@@ -94,6 +131,7 @@ where
 
     #[allow(clippy::too_many_arguments)]
     fn worker_entrypoint(
+        id: usize,
         matcher: T,
         cond: Arc<RwCondvar>,
         input_image: Arc<RwLock<Option<ImageBuffer<Bgra<u8>, Vec<u8>>>>>,
@@ -143,21 +181,28 @@ where
 
                     drop(guard);
 
-                    for candidate in matcher.candidates_iter(&img) {
-                        if matcher.check(&candidate) {
-                            if let Some(result) = matcher.match_image(&candidate) {
-                                // FIXME: This does not overwrite last result if the recevier stalls
-                                if result_tx.try_send((result, last_match)).is_ok() {
-                                    trace!("Found match result");
-                                    if suspendable {
-                                        trace!("Suspending");
-                                        suspend.store(true, std::sync::atomic::Ordering::SeqCst);
-                                    }
-                                };
-                                break;
-                            }
+                    if DEBUG_RENDER.load(std::sync::atomic::Ordering::SeqCst) {
+                        // Traced on a clone, so the matcher is still updated by `find` only
+                        let (_, report) = image_match::debug::trace(&matcher, &img);
+                        let mut reports = DEBUG_REPORTS.lock();
+                        reports.retain(|x| x.0 != id);
+                        // The agent may have been dropped while matching
+                        if !kill.load(std::sync::atomic::Ordering::SeqCst) {
+                            reports.push((id, type_name::<T>(), report));
                         }
                     }
+                    let result = matcher.find(&img);
+
+                    if let Some(result) = result {
+                        // FIXME: This does not overwrite last result if the recevier stalls
+                        if result_tx.try_send((result, last_match)).is_ok() {
+                            trace!("Found match result");
+                            if suspendable {
+                                trace!("Suspending");
+                                suspend.store(true, std::sync::atomic::Ordering::SeqCst);
+                            }
+                        };
+                    }
 
                     buffer = img.into_raw();
                 }
@@ -185,5 +230,64 @@ impl<T: image_match::Matcher<image::ImageBuffer<image::Bgra<u8>, std::vec::Vec<u
 {
     fn drop(&mut self) {
         self.kill.store(true, std::sync::atomic::Ordering::SeqCst);
+        DEBUG_REPORTS.lock().retain(|x| x.0 != self.id);
     }
 }
+
+#[test]
+fn clear_forgets_result() {
+    /// Matches once for each token received, so that no result is left in flight.
+    #[derive(Clone)]
+    struct Gated(Receiver<()>);
+
+    impl Matcher<ImageBuffer<Bgra<u8>, Vec<u8>>> for Gated {
+        type MatchResult = ();
+        type CandidatesIter<'a> = std::iter::Once<image::SubImage<&'a ImageBuffer<Bgra<u8>, Vec<u8>>>>;
+
+        fn view_dimensions(&self) -> (u32, u32) {
+            (1, 1)
+        }
+
+        fn candidates_iter<'a>(
+            &self,
+            view: &'a ImageBuffer<Bgra<u8>, Vec<u8>>,
+        ) -> Self::CandidatesIter<'a> {
+            std::iter::once(image::GenericImageView::view(view, 0, 0, 1, 1))
+        }
+
+        fn match_image<'a>(
+            &self,
+            _view: &image::SubImage<&'a ImageBuffer<Bgra<u8>, Vec<u8>>>,
+        ) -> Option<Self::MatchResult> {
+            // Disconnected at the end of the test
+            self.0.recv().ok()
+        }
+    }
+
+    let cond = Arc::new(RwCondvar::new());
+    let image = Arc::new(RwLock::new(Some(ImageBuffer::new(1, 1))));
+    let (token, gate) = crossbeam_channel::unbounded();
+    let mut agent = MatchAgent::new(Gated(gate), Arc::clone(&cond), image, None, false);
+    let wait_result = |agent: &mut MatchAgent<Gated>| {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while agent.read_result().is_none() {
+            assert!(Instant::now() < deadline);
+            // Notifications are lost while the worker is not waiting
+            cond.cond().notify_all();
+            thread::sleep(Duration::from_millis(10));
+        }
+    };
+
+    token.send(()).unwrap();
+    wait_result(&mut agent);
+    assert!(agent.last_recv().is_some());
+
+    // The worker blocks on the next screen until another token is sent
+    agent.clear();
+    assert!(agent.read_result().is_none());
+    assert!(agent.last_recv().is_none());
+
+    token.send(()).unwrap();
+    wait_result(&mut agent);
+    assert!(agent.last_recv().is_some());
+}
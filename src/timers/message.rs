@@ -0,0 +1,231 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use assets_manager::asset::Png;
+use bus::{Bus, BusReader};
+use image::{Bgra, ImageBuffer};
+use image_match::{
+    message::{MessageMatcher, MessageTemplate, MessageTracker, CHAT_REGION},
+    ocr::ColorKey,
+};
+use once_cell::sync::OnceCell;
+use parking_lot::{Mutex, RwLock};
+
+use crate::rw_condvar::RwCondvar;
+
+use super::{match_agent::MatchAgent, StatusLevel, Timer};
+
+/// Templates of [`SystemMessage::ALL`], loaded once since availability is checked on every frame.
+static MESSAGE_TEMPLATES: OnceCell<Vec<Option<MessageTemplate<SystemMessage>>>> = OnceCell::new();
+
+/// System messages recognized in the chat area.
+///
+/// Templates are read from `assets/messages/`; messages without one are never recognized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemMessage {
+    RuneCooldown,
+    BossEntry,
+    ItemDrop,
+}
+
+impl SystemMessage {
+    pub const ALL: [SystemMessage; 3] = [
+        SystemMessage::RuneCooldown,
+        SystemMessage::BossEntry,
+        SystemMessage::ItemDrop,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SystemMessage::RuneCooldown => "룬 쿨타임",
+            SystemMessage::BossEntry => "보스 입장",
+            SystemMessage::ItemDrop => "아이템 획득",
+        }
+    }
+
+    fn asset(self) -> &'static str {
+        match self {
+            SystemMessage::RuneCooldown => "messages.rune_cooldown",
+            SystemMessage::BossEntry => "messages.boss_entry",
+            SystemMessage::ItemDrop => "messages.item_drop",
+        }
+    }
+
+    fn key(self) -> ColorKey {
+        match self {
+            SystemMessage::RuneCooldown => ColorKey::new([Bgra([68, 221, 255, 255])], 30),
+            SystemMessage::BossEntry => ColorKey::new([Bgra([255, 153, 255, 255])], 30),
+            SystemMessage::ItemDrop => ColorKey::new([Bgra([255, 255, 255, 255])], 30),
+        }
+    }
+
+    fn template(self) -> Option<&'static MessageTemplate<SystemMessage>> {
+        MESSAGE_TEMPLATES.get_or_init(|| {
+            Self::ALL
+                .iter()
+                .map(|&x| {
+                    let image = assets_embedded::assets()
+                        .load::<Png>(x.asset())
+                        .ok()?
+                        .cloned()
+                        .0
+                        .to_bgra8();
+                    MessageTemplate::from_image(x, &image, x.key())
+                })
+                .collect()
+        })[self as usize]
+            .as_ref()
+    }
+
+    /// Whether any message can be recognized.
+    pub fn available() -> bool {
+        Self::ALL.iter().any(|x| x.template().is_some())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MessageEvent {
+    pub message: SystemMessage,
+    pub at: Instant,
+}
+
+struct MessageHubInner {
+    matcher: MatchAgent<MessageMatcher<SystemMessage>>,
+    tracker: MessageTracker,
+    bus: Bus<MessageEvent>,
+}
+
+/// Recognizes system messages and broadcasts them to the subscribers.
+///
+/// The hub is driven by its subscribers, so events are only delivered while someone polls.
+#[derive(Clone)]
+pub struct MessageHub(Arc<Mutex<MessageHubInner>>);
+
+impl MessageHub {
+    pub fn new(
+        cond: Arc<RwCondvar>,
+        image_lock: Arc<RwLock<Option<ImageBuffer<Bgra<u8>, Vec<u8>>>>>,
+    ) -> Self {
+        let templates = SystemMessage::ALL
+            .iter()
+            .filter_map(|x| x.template().cloned())
+            .collect();
+        Self(Arc::new(Mutex::new(MessageHubInner {
+            matcher: MatchAgent::new(
+                MessageMatcher::new(templates, CHAT_REGION),
+                cond,
+                image_lock,
+                Some(Duration::from_millis(300)),
+                false,
+            ),
+            tracker: MessageTracker::default(),
+            bus: Bus::new(64),
+        })))
+    }
+
+    pub fn subscribe(&self) -> MessageSubscription {
+        MessageSubscription {
+            hub: self.clone(),
+            rx: self.0.lock().bus.add_rx(),
+        }
+    }
+
+    fn poll(&self) {
+        let mut inner = self.0.lock();
+        let inner = &mut *inner;
+        if let Some(result) = inner.matcher.read_new_result() {
+            let at = inner.matcher.last_recv().unwrap();
+            for line in inner.tracker.push(&result) {
+                if let Some(message) = line.kind {
+                    // Drop events for subscribers lagging behind rather than blocking
+                    let _ = inner.bus.try_broadcast(MessageEvent { message, at });
+                }
+            }
+        }
+    }
+}
+
+pub struct MessageSubscription {
+    hub: MessageHub,
+    rx: BusReader<MessageEvent>,
+}
+
+impl MessageSubscription {
+    /// Events arrived since the last call.
+    pub fn events(&mut self) -> Vec<MessageEvent> {
+        self.hub.poll();
+        std::iter::from_fn(|| self.rx.try_recv().ok()).collect()
+    }
+}
+
+/// Shows the latest system messages.
+pub struct MessageTimer {
+    subscription: MessageSubscription,
+    recent: Vec<MessageEvent>,
+}
+
+impl MessageTimer {
+    const RECENT: usize = 3;
+
+    pub fn new(hub: &MessageHub) -> Self {
+        Self {
+            subscription: hub.subscribe(),
+            recent: Vec::new(),
+        }
+    }
+
+    fn update(&mut self) {
+        self.recent.extend(self.subscription.events());
+        let len = self.recent.len();
+        self.recent.drain(..len.saturating_sub(Self::RECENT));
+    }
+}
+
+impl Timer for MessageTimer {
+    fn duration(&mut self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn last_match(&mut self) -> Option<Instant> {
+        self.update();
+        self.recent.last().map(|x| x.at)
+    }
+
+    fn remaining_time(&mut self) -> Option<Duration> {
+        None
+    }
+
+    fn text(&self) -> &str {
+        "시스템 메시지"
+    }
+
+    fn is_panicked(&self) -> bool {
+        self.subscription.hub.0.lock().matcher.is_panicked()
+    }
+
+    fn status(&mut self) -> Option<(String, StatusLevel)> {
+        self.update();
+        let now = Instant::now();
+        let status = self
+            .recent
+            .iter()
+            .rev()
+            .map(|x| {
+                format!(
+                    "{} ({}초 전)",
+                    x.message.name(),
+                    now.saturating_duration_since(x.at).as_secs()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        if status.is_empty() {
+            return None;
+        }
+        Some((status, StatusLevel::Normal))
+    }
+
+    fn wake(&mut self) {}
+}
@@ -0,0 +1,85 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use image::{Bgra, ImageBuffer};
+use image_match::minimap::{MinimapEntity, MinimapMatcher};
+use parking_lot::RwLock;
+
+use crate::rw_condvar::RwCondvar;
+
+use super::{match_agent::MatchAgent, StatusLevel, Timer};
+
+/// Watches the minimap for runes, elite bosses and special portals.
+pub struct MinimapTimer {
+    matcher: MatchAgent<MinimapMatcher>,
+}
+
+impl MinimapTimer {
+    pub fn new(
+        cond: Arc<RwCondvar>,
+        image_lock: Arc<RwLock<Option<ImageBuffer<Bgra<u8>, Vec<u8>>>>>,
+    ) -> Self {
+        Self {
+            matcher: MatchAgent::new(
+                MinimapMatcher,
+                cond,
+                image_lock,
+                Some(Duration::from_millis(500)),
+                false,
+            ),
+        }
+    }
+}
+
+impl Timer for MinimapTimer {
+    fn duration(&mut self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn last_match(&mut self) -> Option<Instant> {
+        self.matcher.last_recv()
+    }
+
+    fn remaining_time(&mut self) -> Option<Duration> {
+        None
+    }
+
+    fn text(&self) -> &str {
+        "미니맵"
+    }
+
+    fn is_panicked(&self) -> bool {
+        self.matcher.is_panicked()
+    }
+
+    fn status(&mut self) -> Option<(String, StatusLevel)> {
+        let result = self.matcher.read_result()?;
+        let rune = if result.contains(MinimapEntity::Rune) {
+            "룬 있음"
+        } else {
+            "룬 없음"
+        };
+        let mut status = String::from(rune);
+        let mut level = StatusLevel::Normal;
+        if result.contains(MinimapEntity::SpecialPortal) {
+            status += ", 특수 포탈 등장";
+            level = StatusLevel::Yellow;
+        }
+        if result.contains(MinimapEntity::EliteBoss) {
+            status += ", 엘리트 보스 등장";
+            level = StatusLevel::Red;
+        }
+        Some((status, level))
+    }
+
+    fn debug_string(&mut self) -> String {
+        self.matcher
+            .read_result()
+            .map(|x| format!("bounds: {:?}, entities: {:?}", x.bounds(), x.entities()))
+            .unwrap_or_default()
+    }
+
+    fn wake(&mut self) {}
+}
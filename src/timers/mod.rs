@@ -1,9 +1,25 @@
 use std::time::{Duration, Instant};
 
+pub mod cooldown;
+pub mod debuff;
+pub mod dojang;
+pub mod exp;
 pub mod jinhillah;
 pub mod match_agent;
+pub mod message;
+pub mod minimap;
+pub mod player_gauge;
+pub mod position;
 pub mod vskill;
 
+/// Color of the text shown by [`Timer::status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusLevel {
+    Normal,
+    Yellow,
+    Red,
+}
+
 pub trait Timer {
     fn duration(&mut self) -> Duration;
     fn last_match(&mut self) -> Option<Instant>;
@@ -23,5 +39,11 @@ pub trait Timer {
     fn debug_string(&mut self) -> String {
         String::new()
     }
+    /// Shown instead of the remaining time by trackers which do not count down.
+    fn status(&mut self) -> Option<(String, StatusLevel)> {
+        None
+    }
+    /// Additional widgets shown below the timer row, e.g. charts.
+    fn extra_ui(&mut self, _ui: &mut egui::Ui) {}
     fn wake(&mut self);
 }
@@ -0,0 +1,105 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use image::{Bgra, ImageBuffer};
+use image_match::{
+    filter::{Filter, Hysteresis},
+    gauge::PlayerGaugeMatcher,
+    BoundsCachedMatcher, InvalidationPolicy,
+};
+use parking_lot::RwLock;
+
+use crate::{alert, rw_condvar::RwCondvar};
+
+use super::{match_agent::MatchAgent, StatusLevel, Timer};
+
+/// Shows the player's HP and MP, and beeps when either goes below its threshold.
+pub struct PlayerGaugeTimer {
+    matcher: MatchAgent<BoundsCachedMatcher<PlayerGaugeMatcher>>,
+    hp_threshold: f64,
+    mp_threshold: f64,
+    hp_low: Hysteresis,
+    mp_low: Hysteresis,
+}
+
+impl PlayerGaugeTimer {
+    pub fn new(
+        cond: Arc<RwCondvar>,
+        image_lock: Arc<RwLock<Option<ImageBuffer<Bgra<u8>, Vec<u8>>>>>,
+        hp_threshold: f64,
+        mp_threshold: f64,
+    ) -> Self {
+        Self {
+            matcher: MatchAgent::new(
+                BoundsCachedMatcher::with_policy(
+                    PlayerGaugeMatcher::default(),
+                    InvalidationPolicy {
+                        max_misses: Some(20),
+                        ..Default::default()
+                    },
+                ),
+                cond,
+                image_lock,
+                Some(Duration::from_millis(100)),
+                false,
+            ),
+            hp_threshold,
+            mp_threshold,
+            // A few results in a row, so that a single misread does not beep
+            hp_low: Hysteresis::new(3, 3),
+            mp_low: Hysteresis::new(3, 3),
+        }
+    }
+
+    fn update_alerts(&mut self) {
+        while let Some(result) = self.matcher.read_new_result() {
+            let was_low = self.hp_low.state() || self.mp_low.state();
+            self.hp_low.push(result.hp_ratio() < self.hp_threshold);
+            self.mp_low.push(result.mp_ratio() < self.mp_threshold);
+            let low = self.hp_low.state() || self.mp_low.state();
+            if low && !was_low {
+                alert::beep();
+            }
+        }
+    }
+}
+
+impl Timer for PlayerGaugeTimer {
+    fn duration(&mut self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn last_match(&mut self) -> Option<Instant> {
+        self.matcher.last_recv()
+    }
+
+    fn remaining_time(&mut self) -> Option<Duration> {
+        None
+    }
+
+    fn text(&self) -> &str {
+        "HP/MP"
+    }
+
+    fn is_panicked(&self) -> bool {
+        self.matcher.is_panicked()
+    }
+
+    fn status(&mut self) -> Option<(String, StatusLevel)> {
+        self.update_alerts();
+        let result = self.matcher.read_result()?;
+        let (hp, mp) = (result.hp_ratio(), result.mp_ratio());
+        let level = if hp < self.hp_threshold {
+            StatusLevel::Red
+        } else if mp < self.mp_threshold {
+            StatusLevel::Yellow
+        } else {
+            StatusLevel::Normal
+        };
+        Some((format!("{:.0}% / {:.0}%", hp * 100.0, mp * 100.0), level))
+    }
+
+    fn wake(&mut self) {}
+}
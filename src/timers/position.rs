@@ -0,0 +1,266 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use egui::{Color32, Pos2, Rect, Sense, Shape, Stroke, Ui, Vec2};
+use image::{Bgra, ImageBuffer};
+use image_match::minimap::MinimapPlayerMatcher;
+use parking_lot::RwLock;
+
+use crate::rw_condvar::RwCondvar;
+
+use super::{match_agent::MatchAgent, StatusLevel, Timer};
+
+const HEATMAP_COLUMNS: usize = 40;
+const HEATMAP_ROWS: usize = 20;
+const PATH_LENGTH: usize = 300;
+/// Movements smaller than this, in normalized minimap coordinates, are ignored as jitter.
+const MOVE_EPSILON: f64 = 0.005;
+
+/// Positions of the player on the minimap, and when the player last moved.
+pub struct PositionHistory {
+    /// `(width, height)` of the minimap, for the aspect ratio of the heatmap.
+    minimap_size: (u32, u32),
+    heatmap: Vec<u32>,
+    path: VecDeque<(f64, f64)>,
+    /// `None` until the player is seen.
+    last_move: Option<Instant>,
+    /// Position at `last_move`. Movements are measured from here rather than from the previous
+    /// sample, so that slow movements add up.
+    anchor: Option<(f64, f64)>,
+}
+
+impl Default for PositionHistory {
+    fn default() -> Self {
+        Self {
+            minimap_size: (1, 1),
+            heatmap: vec![0; HEATMAP_COLUMNS * HEATMAP_ROWS],
+            path: VecDeque::new(),
+            last_move: None,
+            anchor: None,
+        }
+    }
+}
+
+impl PositionHistory {
+    pub fn push(&mut self, at: Instant, minimap_size: (u32, u32), player: Option<(f64, f64)>) {
+        // Another map, where the positions of the last one mean nothing
+        if minimap_size != self.minimap_size {
+            *self = Self {
+                minimap_size,
+                last_move: self.last_move,
+                ..Default::default()
+            };
+        }
+
+        let (x, y) = match player {
+            Some(pos) => pos,
+            None => return,
+        };
+        let moved = match self.anchor {
+            Some((px, py)) => (x - px).abs() > MOVE_EPSILON || (y - py).abs() > MOVE_EPSILON,
+            None => true,
+        };
+        if moved {
+            self.last_move = Some(at);
+            self.anchor = Some((x, y));
+        }
+
+        let column = ((x * HEATMAP_COLUMNS as f64) as usize).min(HEATMAP_COLUMNS - 1);
+        let row = ((y * HEATMAP_ROWS as f64) as usize).min(HEATMAP_ROWS - 1);
+        self.heatmap[row * HEATMAP_COLUMNS + column] += 1;
+
+        self.path.push_back((x, y));
+        if self.path.len() > PATH_LENGTH {
+            self.path.pop_front();
+        }
+    }
+
+    /// How long the player has not moved, or `None` if the player has never been seen.
+    pub fn idle_time(&self, now: Instant) -> Option<Duration> {
+        Some(now.saturating_duration_since(self.last_move?))
+    }
+
+    pub fn position(&self) -> Option<(f64, f64)> {
+        self.path.back().copied()
+    }
+}
+
+/// Tracks the player's position on the minimap, and alerts when it has not moved for a while.
+pub struct PositionTimer {
+    matcher: MatchAgent<MinimapPlayerMatcher>,
+    idle_after: Duration,
+    history: PositionHistory,
+    others: Vec<(f64, f64)>,
+}
+
+impl PositionTimer {
+    pub fn new(
+        cond: Arc<RwCondvar>,
+        image_lock: Arc<RwLock<Option<ImageBuffer<Bgra<u8>, Vec<u8>>>>>,
+        idle_after: Duration,
+    ) -> Self {
+        Self {
+            matcher: MatchAgent::new(
+                MinimapPlayerMatcher,
+                cond,
+                image_lock,
+                Some(Duration::from_millis(200)),
+                false,
+            ),
+            idle_after,
+            history: PositionHistory::default(),
+            others: Vec::new(),
+        }
+    }
+
+    fn update(&mut self) {
+        if let Some(result) = self.matcher.read_new_result() {
+            self.others = result.others().to_vec();
+            self.history.push(
+                self.matcher.last_recv().unwrap(),
+                result.size(),
+                result.player(),
+            );
+        }
+    }
+}
+
+impl Timer for PositionTimer {
+    fn duration(&mut self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn last_match(&mut self) -> Option<Instant> {
+        self.update();
+        self.matcher.last_recv()
+    }
+
+    fn remaining_time(&mut self) -> Option<Duration> {
+        None
+    }
+
+    fn text(&self) -> &str {
+        "위치"
+    }
+
+    fn is_panicked(&self) -> bool {
+        self.matcher.is_panicked()
+    }
+
+    fn status(&mut self) -> Option<(String, StatusLevel)> {
+        self.update();
+        match self.history.idle_time(Instant::now()) {
+            Some(idle) if idle >= self.idle_after => {
+                return Some((
+                    format!("{}초째 움직이지 않음", idle.as_secs()),
+                    StatusLevel::Red,
+                ));
+            }
+            _ => (),
+        }
+
+        let (x, y) = self.history.position()?;
+        Some((
+            format!(
+                "({:.0}%, {:.0}%), 다른 플레이어 {}명",
+                x * 100.0,
+                y * 100.0,
+                self.others.len()
+            ),
+            StatusLevel::Normal,
+        ))
+    }
+
+    fn extra_ui(&mut self, ui: &mut Ui) {
+        self.update();
+        let history = &self.history;
+        let (w, h) = history.minimap_size;
+        let width = ui.available_width().min(320.0);
+        let size = Vec2::new(width, width * h.max(1) as f32 / w.max(1) as f32);
+        let (response, painter) = ui.allocate_painter(size, Sense::hover());
+        let rect = response.rect;
+        let to_screen = |(x, y): (f64, f64)| {
+            rect.min + Vec2::new(x as f32 * rect.width(), y as f32 * rect.height())
+        };
+
+        painter.rect_filled(rect, 0.0, Color32::from_gray(20));
+        let max = history.heatmap.iter().copied().max().unwrap_or(0).max(1);
+        let cell = Vec2::new(
+            rect.width() / HEATMAP_COLUMNS as f32,
+            rect.height() / HEATMAP_ROWS as f32,
+        );
+        for (i, &count) in history.heatmap.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let min = rect.min
+                + Vec2::new(
+                    (i % HEATMAP_COLUMNS) as f32 * cell.x,
+                    (i / HEATMAP_COLUMNS) as f32 * cell.y,
+                );
+            let alpha = (count as f32 / max as f32 * 200.0) as u8 + 55;
+            painter.rect_filled(
+                Rect::from_min_size(min, cell),
+                0.0,
+                Color32::from_rgba_unmultiplied(240, 120, 30, alpha),
+            );
+        }
+
+        let path = history
+            .path
+            .iter()
+            .copied()
+            .map(to_screen)
+            .collect::<Vec<Pos2>>();
+        painter.add(Shape::line(
+            path,
+            Stroke::new(1.0_f32, Color32::from_rgb(240, 240, 30)),
+        ));
+        for &pos in &self.others {
+            painter.circle_filled(to_screen(pos), 2.0, Color32::from_rgb(240, 30, 30));
+        }
+        if let Some(pos) = history.position() {
+            painter.circle_filled(to_screen(pos), 3.0, Color32::from_rgb(240, 240, 30));
+        }
+    }
+
+    fn debug_string(&mut self) -> String {
+        format!(
+            "size: {:?}, samples: {}, idle: {:?}",
+            self.history.minimap_size,
+            self.history.heatmap.iter().sum::<u32>(),
+            self.history.idle_time(Instant::now()),
+        )
+    }
+
+    fn wake(&mut self) {}
+}
+
+#[test]
+fn position_idle_time() {
+    let start = Instant::now();
+    let at = |secs| start + Duration::from_secs(secs);
+    let mut history = PositionHistory::default();
+
+    // Not seen yet
+    history.push(at(0), (100, 50), None);
+    assert_eq!(history.idle_time(at(100)), None);
+
+    history.push(at(10), (100, 50), Some((0.5, 0.5)));
+    assert_eq!(history.idle_time(at(15)), Some(Duration::from_secs(5)));
+    // Jitter is not a move, but slow movements add up
+    history.push(at(20), (100, 50), Some((0.504, 0.5)));
+    assert_eq!(history.idle_time(at(20)), Some(Duration::from_secs(10)));
+    history.push(at(30), (100, 50), Some((0.508, 0.5)));
+    assert_eq!(history.idle_time(at(30)), Some(Duration::ZERO));
+    assert_eq!(history.heatmap.iter().sum::<u32>(), 3);
+
+    // Another map
+    history.push(at(40), (80, 80), Some((0.508, 0.5)));
+    assert_eq!(history.path.len(), 1);
+    assert_eq!(history.heatmap.iter().sum::<u32>(), 1);
+    assert_eq!(history.idle_time(at(40)), Some(Duration::ZERO));
+}
@@ -1,22 +1,35 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use assets_manager::asset::Png;
 use image::{Bgra, ImageBuffer};
-use image_match::buff::BuffMatcher;
+use image_match::buff::{BuffMatchResult, BuffScanner, DEFAULT_THRESHOLD};
 use parking_lot::RwLock;
 
 use crate::rw_condvar::RwCondvar;
 
 use super::{match_agent::MatchAgent, Timer};
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum VSkillKind {
     FatalStrike,
 }
 
+impl VSkillKind {
+    fn asset(self) -> &'static str {
+        match self {
+            VSkillKind::FatalStrike => "v_buficon",
+        }
+    }
+}
+
 pub struct VSkillTimer {
-    matcher: MatchAgent<BuffMatcher>,
+    matcher: MatchAgent<BuffScanner<VSkillKind>>,
     kind: VSkillKind,
+    /// When the buff was last seen in the snapshot of active buffs, and how it looked.
+    last_seen: Option<(Instant, BuffMatchResult)>,
 }
 
 impl VSkillTimer {
@@ -26,28 +39,42 @@ impl VSkillTimer {
         kind: VSkillKind,
         dims: (u32, u32),
     ) -> Self {
+        let icon = assets_embedded::assets()
+            .load::<Png>(kind.asset())
+            .unwrap()
+            .cloned()
+            .0
+            .to_bgra8();
         Self {
             matcher: MatchAgent::new(
-                BuffMatcher::new(
-                    assets_embedded::assets()
-                        .load::<Png>("v_buficon")
-                        .unwrap()
-                        .cloned()
-                        .0
-                        .to_bgra8(),
-                    0.8,
-                    dims,
-                ),
+                BuffScanner::new([(kind, icon)], DEFAULT_THRESHOLD, dims),
                 Arc::clone(&cond),
                 Arc::clone(&image_lock),
                 None,
-                true,
+                false,
             ),
             kind,
+            last_seen: None,
+        }
+    }
+
+    fn update(&mut self) {
+        if let Some(active) = self.matcher.read_new_result() {
+            if let Some(buff) = active.into_iter().find(|x| x.kind == self.kind) {
+                let at = self.matcher.last_recv().unwrap_or_else(Instant::now);
+                self.last_seen = Some((at, buff.result));
+            }
         }
     }
 }
 
+/// When a buff seen at `seen` started. The buff might have been active before we first saw it, so
+/// this is estimated from the expiration overlay of the icon.
+fn started_at(seen: Instant, duration: Duration, remaining_ratio: f64) -> Instant {
+    let elapsed = duration.mul_f64(1.0 - remaining_ratio);
+    seen.checked_sub(elapsed).unwrap_or(seen)
+}
+
 impl Timer for VSkillTimer {
     fn duration(&mut self) -> Duration {
         match self.kind {
@@ -55,16 +82,16 @@ impl Timer for VSkillTimer {
         }
     }
 
-    fn last_match(&mut self) -> Option<std::time::Instant> {
-        self.matcher
-            .read_result()
-            .and_then(|_| self.matcher.last_recv())
+    fn last_match(&mut self) -> Option<Instant> {
+        self.update();
+        let (at, result) = self.last_seen.clone()?;
+        Some(started_at(at, self.duration(), result.remaining_ratio()))
     }
 
     fn remaining_time(&mut self) -> Option<Duration> {
         self.last_match()
             .map(|x| x + self.duration())
-            .map(|x| x.saturating_duration_since(std::time::Instant::now()))
+            .map(|x| x.saturating_duration_since(Instant::now()))
     }
 
     fn text(&self) -> &str {
@@ -89,7 +116,51 @@ impl Timer for VSkillTimer {
         String::new()
     }
 
-    fn wake(&mut self) {
-        self.matcher.wake()
-    }
+    fn wake(&mut self) {}
+}
+
+#[test]
+fn start_from_partial_overlay() {
+    use image::GenericImageView;
+    use image_match::{buff::BuffMatcher, Matcher};
+
+    let icon = ImageBuffer::from_fn(32, 32, |x, y| {
+        Bgra([
+            (x * 7) as u8,
+            (x * 5 + y * 3) as u8,
+            (x * x / 4 + 40) as u8,
+            255,
+        ])
+    });
+    // 60% of the icon swept clockwise from 12 o'clock is darkened
+    let covered = ImageBuffer::from_fn(32, 32, |x, y| {
+        let (dx, dy) = (x as f64 - 15.5, y as f64 - 15.5);
+        let angle = dx.atan2(-dy).rem_euclid(std::f64::consts::TAU) / std::f64::consts::TAU;
+        let p = icon.get_pixel(x, y).0;
+        let dark = |c: u8| {
+            if angle < 0.6 {
+                (c as f64 * 0.4) as u8
+            } else {
+                c
+            }
+        };
+        Bgra([dark(p[0]), dark(p[1]), dark(p[2]), 255])
+    });
+    let matcher = BuffMatcher::new(icon, 0.8, (32, 32));
+    let result = Matcher::<ImageBuffer<Bgra<u8>, Vec<u8>>>::match_image(
+        &matcher,
+        &covered.view(0, 0, 32, 32),
+    )
+    .unwrap();
+
+    let seen = Instant::now() + Duration::from_secs(60);
+    let duration = Duration::from_secs(30);
+    let elapsed = seen - started_at(seen, duration, result.remaining_ratio());
+    assert!((elapsed.as_secs_f64() - 18.0).abs() < 3.0);
+
+    assert_eq!(started_at(seen, duration, 1.0), seen);
+    assert_eq!(
+        started_at(seen, duration, 0.5),
+        seen - Duration::from_secs(15)
+    );
 }
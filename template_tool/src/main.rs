@@ -0,0 +1,185 @@
+//! Builds a template from the same region of several screenshots.
+//!
+//! Writes the template with unstable pixels transparent to `<output>`, and the mask of stable
+//! pixels next to it as `<output>_mask.png`. Then prints thresholds for the matchers which would
+//! accept the template on every screenshot.
+
+use std::{path::Path, process::exit};
+
+use image::{imageops, Bgra, DynamicImage, GenericImageView, ImageBuffer, ImageResult};
+use image_match::{buff::BuffMatcher, jinhillah::ReapTemplateConfig, template::Consensus};
+
+const USAGE: &str = "\
+Usage: template_tool <output.png> (--rect X,Y,W,H | --point X,Y --size W,H) [--stable DELTA_E]
+                     <screenshot.png>...
+
+  --rect     Region of the template on the screenshots
+  --point    Center of the region, with --size
+  --stable   Pixels differing more than this among the screenshots are made transparent
+             (CIE76, default 2.0)";
+
+struct Args {
+    output: String,
+    rect: (u32, u32, u32, u32),
+    stable: f64,
+    screenshots: Vec<String>,
+}
+
+fn usage(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    exit(2);
+}
+
+fn parse_numbers<const N: usize>(arg: Option<String>, name: &str) -> [u32; N] {
+    let arg = arg.unwrap_or_else(|| usage(&format!("missing value of {}", name)));
+    let numbers = arg
+        .split(',')
+        .map(|x| x.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|_| usage(&format!("invalid value of {}: {}", name, arg)));
+    numbers
+        .try_into()
+        .unwrap_or_else(|_| usage(&format!("{} takes {} numbers", name, N)))
+}
+
+fn parse_args() -> Args {
+    let mut args = std::env::args().skip(1);
+    let output = args.next().unwrap_or_else(|| usage("missing output"));
+    let (mut rect, mut point, mut size) = (None, None, None);
+    let mut stable = 2.0;
+    let mut screenshots = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rect" => rect = Some(parse_numbers::<4>(args.next(), "--rect")),
+            "--point" => point = Some(parse_numbers::<2>(args.next(), "--point")),
+            "--size" => size = Some(parse_numbers::<2>(args.next(), "--size")),
+            "--stable" => {
+                stable = args
+                    .next()
+                    .and_then(|x| x.parse().ok())
+                    .unwrap_or_else(|| usage("invalid value of --stable"))
+            }
+            _ if arg.starts_with("--") => usage(&format!("unknown option {}", arg)),
+            _ => screenshots.push(arg),
+        }
+    }
+
+    let rect = match (rect, point, size) {
+        (Some([x, y, w, h]), None, None) => (x, y, w, h),
+        (None, Some([x, y]), Some([w, h])) => {
+            (x.saturating_sub(w / 2), y.saturating_sub(h / 2), w, h)
+        }
+        _ => usage("either --rect or both --point and --size are required"),
+    };
+    if rect.2 == 0 || rect.3 == 0 {
+        usage("the region is empty");
+    }
+    if screenshots.is_empty() {
+        usage("no screenshots given");
+    }
+    Args {
+        output,
+        rect,
+        stable,
+        screenshots,
+    }
+}
+
+fn check_saved(result: ImageResult<()>, path: &Path) {
+    if let Err(e) = result {
+        eprintln!("failed to write {}: {}", path.display(), e);
+        exit(1);
+    }
+}
+
+fn main() {
+    let args = parse_args();
+    let (x, y, w, h) = args.rect;
+
+    let screenshots = args
+        .screenshots
+        .iter()
+        .map(|path| match image::open(path) {
+            Ok(img) => (path, img.to_bgra8()),
+            Err(e) => {
+                eprintln!("failed to read {}: {}", path, e);
+                exit(1);
+            }
+        })
+        .collect::<Vec<_>>();
+    let crops = screenshots
+        .iter()
+        .map(|(path, img)| {
+            if x + w > img.width() || y + h > img.height() {
+                eprintln!(
+                    "the region is out of {} ({}x{})",
+                    path,
+                    img.width(),
+                    img.height()
+                );
+                exit(1);
+            }
+            imageops::crop_imm(img, x, y, w, h).to_image()
+        })
+        .collect::<Vec<ImageBuffer<Bgra<u8>, Vec<u8>>>>();
+
+    let consensus = Consensus::from_views(&crops, args.stable).unwrap();
+    let output = Path::new(&args.output);
+    let mask_path = output.with_file_name(format!(
+        "{}_mask.png",
+        output.file_stem().unwrap_or_default().to_string_lossy()
+    ));
+    check_saved(
+        DynamicImage::ImageBgra8(consensus.template().clone())
+            .to_rgba8()
+            .save(output),
+        output,
+    );
+    check_saved(consensus.mask().save(&mask_path), &mask_path);
+    println!(
+        "wrote {} and {} from {} screenshots",
+        output.display(),
+        mask_path.display(),
+        crops.len()
+    );
+
+    let unstable = consensus.unstable_ratio();
+    println!("unstable pixels: {:.1}%", unstable * 100.0);
+    if unstable > 0.5 {
+        println!("warning: most pixels are unstable; check the region or add --stable");
+    }
+
+    // Margins over the worst screenshot, so that unseen screenshots match as well.
+    // The reap matcher magnifies the frames before matching, so compare the magnified template.
+    let reap_template = ReapTemplateConfig::default().magnify(consensus.template());
+    let max_mismatch = crops
+        .iter()
+        .map(|x| ReapTemplateConfig::mismatch_ratio(&reap_template, x))
+        .fold(0.0, f64::max);
+    println!("\nsuggested thresholds:");
+    println!(
+        "  BossHpBarConfig: tolerance {:.1}, icon_mismatch {:.3}",
+        (consensus.max_delta_e() * 1.5 + 2.0).max(4.0),
+        (unstable + 0.02).min(1.0)
+    );
+    println!(
+        "  ReapTemplateConfig: max_mismatch {:.2}",
+        (max_mismatch * 1.5 + 0.05).min(1.0)
+    );
+    println!(
+        "  ColorKey: tolerance {}",
+        consensus.max_channel_diff().saturating_add(8)
+    );
+    if (w, h) == (32, 32) {
+        let matcher = BuffMatcher::new(consensus.template().clone(), 1.0, (0, 0));
+        let min_score = crops
+            .iter()
+            .map(|x| matcher.score(&x.view(0, 0, 32, 32)))
+            .fold(f64::INFINITY, f64::min);
+        println!(
+            "  BuffMatcher: threshold {:.2} (lowest score {:.3})",
+            (min_score - 0.1).max(0.0),
+            min_score
+        );
+    }
+}